@@ -4,10 +4,24 @@ use egui::RichText;
 use std::future::Future;
 use std::sync::mpsc::{channel, Receiver, Sender};
 
+use crate::apu::{ChannelState, ChannelVolume, Frequency, CHANNEL_NAMES, NUM_CHANNELS};
+use crate::audio::AudioOutput;
+use crate::cartridge::{BackupType, FlashChip, RtcClock, SensorInput};
 use crate::gba_emu::Gbaemu;
+use crate::ppu::{LAYER_NAMES, PRIORITY_COLORS, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::save_file;
+use crate::video::{self, ColorCorrection, FrameProcessor, UpscaleFilter, VideoSettings};
 
 type PathBytesChannel = (Sender<(String, Vec<u8>)>, Receiver<(String, Vec<u8>)>);
 
+/// How the screen is sized within the space it is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+enum ScreenScale {
+    /// As large as fits, keeping the aspect ratio.
+    Fit,
+    Integer(u32),
+}
+
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)] // if we add new fields, give them default values when deserializing old state
@@ -18,6 +32,22 @@ pub struct EmulatorApp {
     bios_channel: PathBytesChannel,
     #[serde(skip)]
     rom_channel: PathBytesChannel,
+    #[serde(skip)]
+    save_channel: PathBytesChannel,
+
+    show_io_registers: bool,
+    show_video_debug: bool,
+    show_sound_debug: bool,
+    record_separate_channels: bool,
+    screen_scale: ScreenScale,
+    screen_popped_out: bool,
+    video_settings: VideoSettings,
+    #[serde(skip)]
+    frame_processor: FrameProcessor,
+    #[serde(skip)]
+    screen_texture: Option<egui::TextureHandle>,
+    #[serde(skip)]
+    audio: AudioOutput,
 }
 
 impl Default for EmulatorApp {
@@ -26,6 +56,17 @@ impl Default for EmulatorApp {
             device: Gbaemu::default(),
             bios_channel: channel(),
             rom_channel: channel(),
+            save_channel: channel(),
+            show_io_registers: false,
+            show_video_debug: false,
+            show_sound_debug: false,
+            record_separate_channels: false,
+            screen_scale: ScreenScale::Fit,
+            screen_popped_out: false,
+            video_settings: VideoSettings::default(),
+            frame_processor: FrameProcessor::default(),
+            screen_texture: None,
+            audio: AudioOutput::default(),
         }
     }
 }
@@ -43,19 +84,348 @@ impl EmulatorApp {
         }
         Default::default()
     }
+
+    /// Sensor bindings: `[`/`]` dim or brighten the light, I/J/K/L tilt the console
+    ///   and `,`/`.` rotate it. Tilt and rotation return to rest when their keys are released.
+    fn handle_sensor_keys(&mut self, ctx: &egui::Context) {
+        let mut input = self.device.sensor_input();
+        ctx.input(|i| {
+            if i.key_pressed(egui::Key::OpenBracket) {
+                input.light_level = input.light_level.saturating_sub(8);
+            }
+            if i.key_pressed(egui::Key::CloseBracket) {
+                input.light_level = input.light_level.saturating_add(8);
+            }
+
+            let axis = |negative: egui::Key, positive: egui::Key| -> f32 {
+                (i.key_down(positive) as i32 - i.key_down(negative) as i32) as f32
+            };
+            let tilt_keys = [egui::Key::I, egui::Key::J, egui::Key::K, egui::Key::L];
+            if tilt_keys.iter().any(|key| i.key_down(*key)) {
+                input.tilt_x = axis(egui::Key::J, egui::Key::L);
+                input.tilt_y = axis(egui::Key::I, egui::Key::K);
+            } else if tilt_keys.iter().any(|key| i.key_released(*key)) {
+                input.tilt_x = 0.0;
+                input.tilt_y = 0.0;
+            }
+
+            let gyro_keys = [egui::Key::Comma, egui::Key::Period];
+            if gyro_keys.iter().any(|key| i.key_down(*key)) {
+                input.gyro_rate = axis(egui::Key::Comma, egui::Key::Period);
+            } else if gyro_keys.iter().any(|key| i.key_released(*key)) {
+                input.gyro_rate = 0.0;
+            }
+        });
+        self.device.set_sensor_input(input);
+    }
+
+    fn show_sensor_window(&mut self, ctx: &egui::Context) {
+        let has_sensors = self.device.has_solar_sensor()
+            || self.device.has_tilt_sensor()
+            || self.device.has_gyro_sensor()
+            || self.device.has_rumble();
+        if !has_sensors {
+            return;
+        }
+
+        let mut input = self.device.sensor_input();
+        egui::Window::new("Cartridge Sensors").show(ctx, |ui| {
+            if self.device.has_solar_sensor() {
+                ui.add(egui::Slider::new(&mut input.light_level, 0..=255).text("Light level"));
+            }
+
+            if self.device.has_tilt_sensor() {
+                ui.label("Tilt (drag, double-click to level):");
+                tilt_pad(ui, &mut input);
+            }
+
+            if self.device.has_gyro_sensor() {
+                ui.add(egui::Slider::new(&mut input.gyro_rate, -1.0..=1.0).text("Rotation"));
+            }
+
+            if self.device.has_rumble() {
+                let (text, color) = if self.device.rumble_active() {
+                    ("Rumble: ON", Color32::RED)
+                } else {
+                    ("Rumble: off", Color32::GRAY)
+                };
+                ui.label(RichText::new(text).color(color));
+            }
+        });
+        self.device.set_sensor_input(input);
+    }
+
+    fn show_io_register_window(&mut self, ctx: &egui::Context) {
+        egui::Window::new("IO Registers")
+            .open(&mut self.show_io_registers)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("io_registers")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (register, value) in self.device.io_registers() {
+                                let access = match (register.is_readable(), register.is_writable())
+                                {
+                                    (true, true) => "R/W",
+                                    (true, false) => "R",
+                                    (false, true) => "W",
+                                    (false, false) => "-",
+                                };
+                                let value = match register.size {
+                                    1 => format!("{:02x}", value),
+                                    2 => format!("{:04x}", value),
+                                    _ => format!("{:08x}", value),
+                                };
+                                ui.monospace(format!("{:#010x}", register.address));
+                                ui.monospace(register.name);
+                                ui.monospace(access);
+                                ui.monospace(value);
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+    }
+
+    /// Switches for each layer and effect, plus views that pick layers apart.
+    fn show_video_debug_window(&mut self, ctx: &egui::Context) {
+        let debug = self.device.render_debug_mut();
+        egui::Window::new("Video")
+            .open(&mut self.show_video_debug)
+            .show(ctx, |ui| {
+                ui.label(RichText::new("Layers").color(Color32::GREEN));
+                ui.horizontal(|ui| {
+                    for (enabled, name) in debug.layers.iter_mut().zip(LAYER_NAMES) {
+                        ui.checkbox(enabled, name);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut debug.windows, "Windows");
+                    ui.checkbox(&mut debug.blending, "Blending");
+                });
+
+                ui.separator();
+                egui::ComboBox::from_label("Show only")
+                    .selected_text(
+                        debug
+                            .isolated_layer
+                            .map_or("All layers", |layer| LAYER_NAMES[layer]),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut debug.isolated_layer, None, "All layers");
+                        for (layer, name) in LAYER_NAMES.iter().enumerate() {
+                            ui.selectable_value(&mut debug.isolated_layer, Some(layer), *name);
+                        }
+                    });
+
+                ui.separator();
+                ui.checkbox(&mut debug.priority_heatmap, "Priority heatmap");
+                ui.horizontal(|ui| {
+                    for (priority, color) in PRIORITY_COLORS.iter().enumerate() {
+                        ui.label(
+                            RichText::new(format!("■ {}", priority))
+                                .color(video::bgr555_to_color32(*color)),
+                        );
+                    }
+                });
+            });
+    }
+
+    /// Each channel's waveform, pitch and volume with switches to mute or solo it, and an
+    ///   editor for wave RAM.
+    fn show_sound_debug_window(&mut self, ctx: &egui::Context) {
+        if !self.show_sound_debug {
+            return;
+        }
+        let channels = self.device.sound_channels();
+        let scopes: Vec<Vec<f32>> = (0..NUM_CHANNELS)
+            .map(|channel| self.device.sound_scope(channel))
+            .collect();
+        let (mut banks, played_bank) = self.device.wave_ram();
+        let original_banks = banks;
+        let mut debug = *self.device.sound_debug_mut();
+
+        egui::Window::new("Sound")
+            .open(&mut self.show_sound_debug)
+            .show(ctx, |ui| {
+                egui::Grid::new("sound_channels")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (channel, name) in CHANNEL_NAMES.iter().enumerate() {
+                            let state = &channels[channel];
+                            let color = if state.playing {
+                                Color32::GREEN
+                            } else {
+                                Color32::GRAY
+                            };
+                            ui.label(RichText::new(*name).color(color));
+                            ui.checkbox(&mut debug.muted[channel], "Mute");
+                            ui.checkbox(&mut debug.soloed[channel], "Solo");
+                            show_scope(ui, &scopes[channel]);
+                            ui.monospace(frequency_text(state));
+                            ui.monospace(volume_text(state.volume));
+                            ui.end_row();
+                        }
+                    });
+
+                ui.separator();
+                ui.label(RichText::new("Wave RAM").color(Color32::GREEN));
+                for (bank, bytes) in banks.iter_mut().enumerate() {
+                    let role = if bank == played_bank {
+                        "played first"
+                    } else {
+                        "seen by the CPU"
+                    };
+                    ui.label(format!("Bank {} ({})", bank, role));
+                    edit_wave_bank(ui, bytes);
+                    let hex: Vec<String> =
+                        bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                    ui.monospace(hex.join(" "));
+                }
+            });
+
+        *self.device.sound_debug_mut() = debug;
+        for (bank, bytes) in banks.into_iter().enumerate() {
+            if bytes != original_banks[bank] {
+                self.device.set_wave_ram(bank, bytes);
+            }
+        }
+    }
+
+    /// Asks where to save a finished audio recording. Channels recorded separately are saved
+    ///   beside it, named after their channel.
+    fn save_audio_recording(&self, files: Vec<(&'static str, Vec<u8>)>) {
+        let file_name = self
+            .device
+            .rompath()
+            .with_extension("wav")
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or("recording.wav".to_owned());
+        let task = rfd::AsyncFileDialog::new()
+            .add_filter("WAV audio", &["wav"])
+            .set_file_name(file_name)
+            .save_file();
+        execute(async move {
+            let Some(file) = task.await else {
+                return;
+            };
+            let chosen_name = file.file_name();
+            let stem = chosen_name.strip_suffix(".wav").unwrap_or(&chosen_name);
+            for (index, (channel, bytes)) in files.into_iter().enumerate() {
+                let result = if index == 0 {
+                    file.write(&bytes).await
+                } else {
+                    let channel = channel.to_lowercase().replace(' ', "-");
+                    write_beside(&file, format!("{}-{}.wav", stem, channel), &bytes).await
+                };
+                if let Err(err) = result {
+                    println!("Could not save audio recording: {}", err);
+                }
+            }
+        });
+    }
+
+    /// Uploads the latest frame, creating the texture on first use.
+    fn update_screen_texture(&mut self, ctx: &egui::Context) {
+        let Some(image) = self.frame_processor.process(
+            self.device.frame(),
+            self.device.previous_frame(),
+            self.device.frame_count(),
+            &self.video_settings,
+        ) else {
+            return;
+        };
+        match &mut self.screen_texture {
+            Some(texture) => texture.set(image, egui::TextureOptions::NEAREST),
+            None => {
+                self.screen_texture =
+                    Some(ctx.load_texture("screen", image, egui::TextureOptions::NEAREST))
+            }
+        }
+    }
+
+    /// Shows the screen in its own viewport. Where the platform can't open one, such as on
+    ///   the web, it is shown in a window instead.
+    fn show_screen_viewport(&mut self, ctx: &egui::Context) {
+        let Some(texture) = &self.screen_texture else {
+            return;
+        };
+        let scale = self.screen_scale;
+        let popped_out = &mut self.screen_popped_out;
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("screen_viewport"),
+            egui::ViewportBuilder::default()
+                .with_title("GBA Screen")
+                .with_inner_size([SCREEN_WIDTH as f32 * 2.0, SCREEN_HEIGHT as f32 * 2.0]),
+            |ctx, class| {
+                if class == egui::ViewportClass::Embedded {
+                    egui::Window::new("GBA Screen")
+                        .open(popped_out)
+                        .resizable(true)
+                        .show(ctx, |ui| show_screen(ui, texture, scale));
+                    return;
+                }
+
+                egui::CentralPanel::default()
+                    .frame(egui::Frame::none().fill(Color32::BLACK))
+                    .show(ctx, |ui| show_screen(ui, texture, scale));
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    *popped_out = false;
+                }
+            },
+        );
+    }
+
+    fn store_save(&self, storage: Option<&mut dyn eframe::Storage>, savebytes: &[u8]) {
+        if let Err(err) = save_file::store(storage, self.device.rompath(), savebytes) {
+            println!("Could not store battery save: {}", err);
+        }
+    }
 }
 
 impl eframe::App for EmulatorApp {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         eframe::set_value(storage, eframe::APP_KEY, self);
+
+        // Also called on shutdown, so don't lose a battery save that is still waiting to go idle
+        if let Some(savebytes) = self.device.take_pending_save() {
+            self.store_save(Some(storage), &savebytes);
+        }
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         // Put your widgets into a `SidePanel`, `TopBottomPanel`, `CentralPanel`, `Window` or `Area`.
         // For inspiration and more examples, go to https://emilk.github.io/egui
 
+        if let Some(savebytes) = self.device.poll_idle_save(ctx.input(|i| i.time)) {
+            self.store_save(frame.storage_mut().map(|storage| storage as _), &savebytes);
+        }
+        if self.device.has_pending_save() {
+            // Make sure the idle flush happens even if nothing else triggers a repaint
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+
+        if let Some(files) = self.device.take_audio_recording() {
+            self.save_audio_recording(files);
+        }
+        let samples = self.device.take_audio_samples();
+        self.audio
+            .process(self.device.audio_sample_rate(), &samples);
+
+        if let Ok((path, bytes)) = self.save_channel.1.try_recv() {
+            match self.device.import_save(path, &bytes) {
+                // Persist right away so the imported save replaces the game's existing one
+                Ok(()) => self.store_save(
+                    frame.storage_mut().map(|storage| storage as _),
+                    &self.device.save_data(),
+                ),
+                Err(err) => println!("Could not import save: {}", err),
+            }
+        }
+
         if ctx.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
             self.device.advance_mem_cursor()
         }
@@ -79,20 +449,193 @@ impl eframe::App for EmulatorApp {
             self.device.reset()
         }
 
+        self.handle_sensor_keys(ctx);
+        self.show_sensor_window(ctx);
+        self.show_io_register_window(ctx);
+        self.show_video_debug_window(ctx);
+        self.show_sound_debug_window(ctx);
+
+        self.update_screen_texture(ctx);
+        if self.screen_popped_out {
+            self.show_screen_viewport(ctx);
+        }
+
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             // The top panel is often a good place for a menu bar:
 
             egui::menu::bar(ui, |ui| {
                 // NOTE: no File->Quit on web pages!
                 let is_web = cfg!(target_arch = "wasm32");
-                if !is_web {
-                    ui.menu_button("File", |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("Import .sav").clicked() {
+                        let sender = self.save_channel.0.clone();
+                        let task = rfd::AsyncFileDialog::new()
+                            .add_filter("Battery save", &["sav"])
+                            .pick_file();
+                        let ctx = ui.ctx().clone();
+                        execute(async move {
+                            let file = task.await;
+                            if let Some(file) = file {
+                                let path = file_path(&file);
+                                let bytes = file.read().await;
+                                let _ = sender.send((path, bytes));
+                                ctx.request_repaint();
+                            }
+                        });
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Export .sav").clicked() {
+                        let savebytes = self.device.export_save_data();
+                        let file_name = self
+                            .device
+                            .rompath()
+                            .with_extension("sav")
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or("game.sav".to_owned());
+                        let task = rfd::AsyncFileDialog::new()
+                            .set_file_name(file_name)
+                            .save_file();
+                        execute(async move {
+                            let file = task.await;
+                            if let Some(file) = file {
+                                if let Err(err) = file.write(&savebytes).await {
+                                    println!("Could not export save: {}", err);
+                                }
+                            }
+                        });
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+                    if self.device.is_recording_audio() {
+                        if ui.button("Stop audio recording").clicked() {
+                            // Emulation only advances by single steps, so there is no
+                            //   running frame to wait for
+                            self.device.stop_audio_recording(false);
+                            ui.close_menu();
+                        }
+                    } else if ui.button("Start audio recording").clicked() {
+                        self.device
+                            .start_audio_recording(self.record_separate_channels);
+                        ui.close_menu();
+                    }
+                    ui.add_enabled(
+                        !self.device.is_recording_audio(),
+                        egui::Checkbox::new(
+                            &mut self.record_separate_channels,
+                            "Record channels separately",
+                        ),
+                    );
+
+                    if !is_web {
+                        ui.separator();
                         if ui.button("Quit").clicked() {
                             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         }
-                    });
-                    ui.add_space(16.0);
-                }
+                    }
+                });
+                ui.add_space(16.0);
+
+                ui.menu_button("Cartridge", |ui| {
+                    ui.label("Backup type:");
+                    let mut backup_override = self.device.backup_override();
+                    ui.radio_value(
+                        &mut backup_override,
+                        None,
+                        format!("Auto ({})", self.device.detected_backup_type()),
+                    );
+                    for backup_type in BackupType::ALL {
+                        ui.radio_value(
+                            &mut backup_override,
+                            Some(backup_type),
+                            backup_type.to_string(),
+                        );
+                    }
+                    if backup_override != self.device.backup_override() {
+                        self.device.set_backup_override(backup_override);
+                    }
+
+                    ui.separator();
+                    ui.label("Flash chip:");
+                    let mut flash_chip = self.device.flash_chip();
+                    ui.radio_value(&mut flash_chip, None, "Auto");
+                    for chip in FlashChip::ALL {
+                        ui.radio_value(&mut flash_chip, Some(chip), chip.to_string());
+                    }
+                    if flash_chip != self.device.flash_chip() {
+                        self.device.set_flash_chip(flash_chip);
+                    }
+
+                    ui.separator();
+                    ui.label("Real-time clock:");
+                    let mut rtc_override = self.device.rtc_override();
+                    let detected = if self.device.detected_rtc() {
+                        "present"
+                    } else {
+                        "absent"
+                    };
+                    ui.radio_value(&mut rtc_override, None, format!("Auto ({})", detected));
+                    ui.radio_value(&mut rtc_override, Some(true), "Present");
+                    ui.radio_value(&mut rtc_override, Some(false), "Absent");
+                    if rtc_override != self.device.rtc_override() {
+                        self.device.set_rtc_override(rtc_override);
+                    }
+
+                    let mut rtc_clock = self.device.rtc_clock();
+                    for clock in [RtcClock::Host, RtcClock::Fixed] {
+                        ui.radio_value(&mut rtc_clock, clock, clock.to_string());
+                    }
+                    if rtc_clock != self.device.rtc_clock() {
+                        self.device.set_rtc_clock(rtc_clock);
+                    }
+                });
+
+                ui.add_space(16.0);
+
+                ui.menu_button("View", |ui| {
+                    ui.label("Screen size:");
+                    ui.radio_value(&mut self.screen_scale, ScreenScale::Fit, "Fit to window");
+                    for factor in 1..=6 {
+                        ui.radio_value(
+                            &mut self.screen_scale,
+                            ScreenScale::Integer(factor),
+                            format!("{}x", factor),
+                        );
+                    }
+
+                    ui.separator();
+                    ui.checkbox(&mut self.screen_popped_out, "Pop out screen");
+
+                    ui.separator();
+                    ui.label("Color correction:");
+                    for correction in ColorCorrection::ALL {
+                        ui.radio_value(
+                            &mut self.video_settings.color_correction,
+                            correction,
+                            correction.name(),
+                        );
+                    }
+                    ui.checkbox(&mut self.video_settings.frame_blending, "Frame blending");
+
+                    ui.separator();
+                    ui.label("Filter:");
+                    for filter in UpscaleFilter::ALL {
+                        ui.radio_value(
+                            &mut self.video_settings.upscale_filter,
+                            filter,
+                            filter.name(),
+                        );
+                    }
+                });
+                ui.add_space(16.0);
+
+                ui.menu_button("Debug", |ui| {
+                    ui.checkbox(&mut self.show_io_registers, "IO registers");
+                    ui.checkbox(&mut self.show_video_debug, "Video");
+                    ui.checkbox(&mut self.show_sound_debug, "Sound");
+                });
 
                 //egui::widgets::global_dark_light_mode_buttons(ui);
                 ctx.set_visuals(egui::style::Visuals::dark());
@@ -111,6 +654,21 @@ impl eframe::App for EmulatorApp {
                 );
             });
 
+        egui::TopBottomPanel::bottom("execution_view")
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.label(RichText::new("Execution View").color(Color32::GREEN));
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.device.get_execution_state())
+                            .font(egui::TextStyle::Monospace)
+                            .desired_width(640.0),
+                    );
+                });
+                ui.separator();
+                ui.label(self.device.get_status());
+            });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             // The central panel the region left after adding TopPanel's and SidePanel's
             ui.heading("egui GBA Emulator");
@@ -132,7 +690,7 @@ impl eframe::App for EmulatorApp {
                 execute(async move {
                     let file = task.await;
                     if let Some(file) = file {
-                        let path = file.file_name();
+                        let path = file_path(&file);
                         let bytes = file.read().await;
                         let _ = sender.send((path, bytes));
                         ctx.request_repaint();
@@ -141,9 +699,24 @@ impl eframe::App for EmulatorApp {
             }
 
             if let Ok((path, bytes)) = self.rom_channel.1.try_recv() {
+                // Flush the outgoing game's save before its path is replaced
+                if let Some(savebytes) = self.device.take_pending_save() {
+                    self.store_save(frame.storage_mut().map(|storage| storage as _), &savebytes);
+                }
+
                 self.device
                     .load_rom(path, &bytes)
                     .expect("Could not load rom");
+
+                if let Some(savebytes) = save_file::load(frame.storage(), self.device.rompath()) {
+                    let savepath = self.device.rompath().with_extension("sav");
+                    if let Err(err) = self
+                        .device
+                        .import_save(savepath.display().to_string(), &savebytes)
+                    {
+                        println!("Could not load battery save: {}", err);
+                    }
+                }
             }
 
             if ui.button("Open ROM File").clicked() {
@@ -153,7 +726,7 @@ impl eframe::App for EmulatorApp {
                 execute(async move {
                     let file = task.await;
                     if let Some(file) = file {
-                        let path = file.file_name();
+                        let path = file_path(&file);
                         let bytes = file.read().await;
                         let _ = sender.send((path, bytes));
                         ctx.request_repaint();
@@ -162,21 +735,212 @@ impl eframe::App for EmulatorApp {
             }
 
             ui.separator();
-            ui.label(RichText::new("Execution View").color(Color32::GREEN));
-            ui.add(
-                egui::TextEdit::multiline(&mut self.device.get_execution_state())
-                    .font(egui::TextStyle::Monospace)
-                    .desired_width(640.0),
-            );
-
-            ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
-                ui.label(self.device.get_status());
-                ui.separator();
-            });
+            if let Some(texture) = self
+                .screen_texture
+                .as_ref()
+                .filter(|_| !self.screen_popped_out)
+            {
+                show_screen(ui, texture, self.screen_scale);
+            }
         });
     }
 }
 
+/// Paints the screen centered in the remaining space of `ui`.
+fn show_screen(ui: &mut egui::Ui, texture: &egui::TextureHandle, scale: ScreenScale) {
+    let native = egui::vec2(SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32);
+    let (rect, _) = ui.allocate_exact_size(ui.available_size(), egui::Sense::hover());
+    let size = match scale {
+        ScreenScale::Fit => native * (rect.width() / native.x).min(rect.height() / native.y),
+        ScreenScale::Integer(factor) => native * factor as f32,
+    };
+
+    let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+    ui.painter_at(rect).image(
+        texture.id(),
+        egui::Rect::from_center_size(rect.center(), size),
+        uv,
+        Color32::WHITE,
+    );
+}
+
+/// Draws a channel's recent output, starting from a rising zero crossing so periodic
+///   waves hold still.
+fn show_scope(ui: &mut egui::Ui, samples: &[f32]) {
+    let shown = samples.len() / 2;
+    let start = (1..samples.len() - shown)
+        .find(|&index| samples[index - 1] < 0.0 && samples[index] >= 0.0)
+        .unwrap_or(samples.len() - shown);
+
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(256.0, 40.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, Color32::BLACK);
+    let points: Vec<egui::Pos2> = samples[start..start + shown]
+        .iter()
+        .enumerate()
+        .map(|(index, sample)| {
+            egui::pos2(
+                rect.left() + rect.width() * index as f32 / (shown - 1) as f32,
+                rect.center().y - sample * rect.height() / 2.0,
+            )
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1.0, Color32::LIGHT_GREEN),
+    ));
+}
+
+/// The frequency, with the nearest note for channels that play one.
+fn frequency_text(state: &ChannelState) -> String {
+    match state.frequency {
+        Frequency::Hz(hz) if state.tonal => format!("{:>9.1} Hz {}", hz, note_name(hz)),
+        Frequency::Hz(hz) => format!("{:>9.1} Hz", hz),
+        Frequency::TimerStopped => "timer stopped".to_owned(),
+        Frequency::TimerCountsUp => "timer counts up".to_owned(),
+    }
+}
+
+/// The note nearest to `hz` in equal temperament, and how far off it is in cents.
+fn note_name(hz: f64) -> String {
+    const NAMES: [&str; 12] = [
+        "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+    ];
+    // MIDI note numbers, where 69 is A4 at 440 Hz
+    let note = 69.0 + 12.0 * (hz / 440.0).log2();
+    let nearest = note.round();
+    let cents = ((note - nearest) * 100.0).round();
+    let octave = (nearest / 12.0).floor() as i32 - 1;
+    let name = NAMES[nearest.rem_euclid(12.0) as usize];
+    format!("{}{} {:+}c", name, octave, cents)
+}
+
+fn volume_text(volume: ChannelVolume) -> String {
+    match volume {
+        ChannelVolume::Envelope {
+            volume, period: 0, ..
+        } => {
+            format!("{:>2}/15", volume)
+        }
+        ChannelVolume::Envelope {
+            volume,
+            increase,
+            period,
+        } => {
+            let direction = if increase { "up" } else { "down" };
+            format!("{:>2}/15, {} every {}/64 s", volume, direction, period)
+        }
+        ChannelVolume::Percent(percent) => format!("{}%", percent),
+        ChannelVolume::Fifo { percent, queued } => {
+            format!("{}%, {:>2} queued", percent, queued)
+        }
+    }
+}
+
+/// Shows the 32 4-bit samples of a wave RAM bank as bars, which can be clicked or dragged
+///   to set them. The high nibble of each byte is the earlier sample.
+fn edit_wave_bank(ui: &mut egui::Ui, bytes: &mut [u8; 16]) {
+    const BAR_WIDTH: f32 = 8.0;
+    const BAR_HEIGHT: f32 = 4.0;
+    let size = egui::vec2(32.0 * BAR_WIDTH, 16.0 * BAR_HEIGHT);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+
+    if let Some(pos) = response.interact_pointer_pos() {
+        let index = ((pos.x - rect.left()) / BAR_WIDTH).clamp(0.0, 31.0) as usize;
+        let value = (15.0 - ((pos.y - rect.top()) / BAR_HEIGHT).floor()).clamp(0.0, 15.0) as u8;
+        let byte = &mut bytes[index / 2];
+        *byte = if index % 2 == 0 {
+            (*byte & 0x0f) | (value << 4)
+        } else {
+            (*byte & 0xf0) | value
+        };
+    }
+
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, Color32::BLACK);
+    for index in 0..32 {
+        let byte = bytes[index / 2];
+        let value = if index % 2 == 0 {
+            byte >> 4
+        } else {
+            byte & 0xf
+        };
+        let left = rect.left() + index as f32 * BAR_WIDTH;
+        let top = rect.bottom() - (value as f32 + 1.0) * BAR_HEIGHT;
+        painter.rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(left + 1.0, top),
+                egui::pos2(left + BAR_WIDTH - 1.0, rect.bottom()),
+            ),
+            0.0,
+            Color32::LIGHT_GREEN,
+        );
+    }
+}
+
+/// A square pad mapping the pointer position to the two tilt axes.
+fn tilt_pad(ui: &mut egui::Ui, input: &mut SensorInput) {
+    const PAD_SIZE: f32 = 120.0;
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(PAD_SIZE, PAD_SIZE),
+        egui::Sense::click_and_drag(),
+    );
+
+    if response.double_clicked() {
+        input.tilt_x = 0.0;
+        input.tilt_y = 0.0;
+    } else if let Some(pos) = response.interact_pointer_pos() {
+        let offset = (pos - rect.center()) / (PAD_SIZE / 2.0);
+        input.tilt_x = offset.x.clamp(-1.0, 1.0);
+        input.tilt_y = offset.y.clamp(-1.0, 1.0);
+    }
+
+    let painter = ui.painter_at(rect);
+    let stroke = egui::Stroke::new(1.0, Color32::GRAY);
+    painter.rect_stroke(rect, 0.0, stroke);
+    painter.hline(rect.x_range(), rect.center().y, stroke);
+    painter.vline(rect.center().x, rect.y_range(), stroke);
+    let marker = rect.center() + egui::vec2(input.tilt_x, input.tilt_y) * (PAD_SIZE / 2.0);
+    painter.circle_filled(marker, 4.0, Color32::GREEN);
+}
+
+/// The full path where the platform provides one, otherwise just the file name.
+#[cfg(not(target_arch = "wasm32"))]
+fn file_path(file: &rfd::FileHandle) -> String {
+    file.path().display().to_string()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn file_path(file: &rfd::FileHandle) -> String {
+    file.file_name()
+}
+
+/// Writes a file into the same directory as `file`.
+#[cfg(not(target_arch = "wasm32"))]
+async fn write_beside(
+    file: &rfd::FileHandle,
+    file_name: String,
+    bytes: &[u8],
+) -> std::io::Result<()> {
+    std::fs::write(file.path().with_file_name(file_name), bytes)
+}
+
+/// The browser has no directories to write into, so each file gets its own download.
+#[cfg(target_arch = "wasm32")]
+async fn write_beside(
+    _file: &rfd::FileHandle,
+    file_name: String,
+    bytes: &[u8],
+) -> std::io::Result<()> {
+    let task = rfd::AsyncFileDialog::new()
+        .set_file_name(file_name)
+        .save_file();
+    match task.await {
+        Some(file) => file.write(bytes).await,
+        None => Ok(()),
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 fn execute<F: Future<Output = ()> + Send + 'static>(f: F) {
     std::thread::spawn(move || futures::executor::block_on(f));
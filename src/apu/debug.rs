@@ -0,0 +1,103 @@
+use super::NUM_CHANNELS;
+use crate::timer::TimerRate;
+
+/// Samples of each channel kept for the oscilloscopes.
+pub const SCOPE_LENGTH: usize = 1024;
+
+/// Playback switches for telling the channels apart. They only change what is heard, never
+///   what the emulated hardware sees, and leave separately recorded channels alone.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SoundDebug {
+    pub muted: [bool; NUM_CHANNELS],
+    pub soloed: [bool; NUM_CHANNELS],
+}
+
+impl SoundDebug {
+    /// Once any channel is soloed, only soloed channels are heard.
+    pub fn audible(&self, channel: usize) -> bool {
+        if self.soloed.contains(&true) {
+            self.soloed[channel]
+        } else {
+            !self.muted[channel]
+        }
+    }
+}
+
+/// The recent output of each channel, before routing and volume, scaled to -1 to 1.
+#[derive(Debug, Clone)]
+pub struct Scopes {
+    samples: [[f32; SCOPE_LENGTH]; NUM_CHANNELS],
+    // Where the next sample goes, over the oldest one
+    next: usize,
+}
+
+impl Default for Scopes {
+    fn default() -> Self {
+        Self {
+            samples: [[0.0; SCOPE_LENGTH]; NUM_CHANNELS],
+            next: 0,
+        }
+    }
+}
+
+impl Scopes {
+    pub fn push(&mut self, outputs: [f32; NUM_CHANNELS]) {
+        for (samples, output) in self.samples.iter_mut().zip(outputs) {
+            samples[self.next] = output;
+        }
+        self.next = (self.next + 1) % SCOPE_LENGTH;
+    }
+
+    /// A channel's samples, oldest first.
+    pub fn channel(&self, channel: usize) -> Vec<f32> {
+        let samples = &self.samples[channel];
+        [&samples[self.next..], &samples[..self.next]].concat()
+    }
+}
+
+/// How loud a channel is set to play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelVolume {
+    /// The current level of a square or noise channel, out of 15, and where it is heading.
+    Envelope {
+        volume: u32,
+        increase: bool,
+        period: u32,
+    },
+    /// The wave channel's volume setting.
+    Percent(u32),
+    /// A FIFO's volume setting, and the samples it has queued.
+    Fifo { percent: u32, queued: usize },
+}
+
+/// What a channel's pitch or sample rate reads as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frequency {
+    Hz(f64),
+    /// A FIFO paced by a timer that isn't running.
+    TimerStopped,
+    /// A FIFO paced by a timer counting overflows of the one before it.
+    TimerCountsUp,
+}
+
+impl From<TimerRate> for Frequency {
+    fn from(rate: TimerRate) -> Self {
+        match rate {
+            TimerRate::Stopped => Self::TimerStopped,
+            TimerRate::CountUp => Self::TimerCountsUp,
+            TimerRate::Hz(hz) => Self::Hz(hz),
+        }
+    }
+}
+
+/// A snapshot of one channel for the sound debugger.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelState {
+    pub playing: bool,
+    /// The pitch for the square and wave channels, the shift rate for noise and the sample
+    ///   rate for the FIFOs.
+    pub frequency: Frequency,
+    /// Whether `frequency` is a musical pitch.
+    pub tonal: bool,
+    pub volume: ChannelVolume,
+}
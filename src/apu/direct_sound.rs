@@ -0,0 +1,41 @@
+use std::collections::VecDeque;
+
+const FIFO_CAPACITY: usize = 32;
+/// A FIFO asks for a refill once it is half empty.
+const REFILL_LEVEL: usize = 16;
+
+/// One of the two Direct Sound channels: a FIFO of signed 8-bit samples, one of which is
+///   played each time the selected timer overflows.
+#[derive(Debug, Default, Clone)]
+pub struct DirectSound {
+    fifo: VecDeque<i8>,
+    /// The sample being played.
+    pub sample: i8,
+}
+
+impl DirectSound {
+    /// Samples written to a full FIFO are lost.
+    pub fn push(&mut self, sample: i8) {
+        if self.fifo.len() < FIFO_CAPACITY {
+            self.fifo.push_back(sample);
+        }
+    }
+
+    /// Samples waiting to be played.
+    pub fn len(&self) -> usize {
+        self.fifo.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.fifo.clear();
+    }
+
+    /// Moves on to the next sample, returning whether the FIFO wants a refill. An empty
+    ///   FIFO keeps playing its last sample.
+    pub fn timer_overflow(&mut self) -> bool {
+        if let Some(sample) = self.fifo.pop_front() {
+            self.sample = sample;
+        }
+        self.fifo.len() <= REFILL_LEVEL
+    }
+}
@@ -0,0 +1,97 @@
+use crate::util::get_bits;
+
+/// Steps a channel's volume up or down every `period` 64 Hz ticks.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Envelope {
+    initial_volume: u32,
+    pub increase: bool,
+    /// 64 Hz ticks per volume step, or 0 for a constant volume.
+    pub period: u32,
+    timer: u32,
+    pub volume: u32,
+}
+
+impl Envelope {
+    /// Takes the envelope byte of SOUND1CNT_H, SOUND2CNT_L or SOUND4CNT_L. The new settings
+    ///   only apply from the next trigger.
+    pub fn write(&mut self, value: u32) {
+        self.period = get_bits(value, 0, 3);
+        self.increase = get_bits(value, 3, 1) != 0;
+        self.initial_volume = get_bits(value, 4, 4);
+    }
+
+    /// A channel whose envelope starts silent and fades out has its DAC turned off.
+    pub fn dac_enabled(&self) -> bool {
+        self.initial_volume != 0 || self.increase
+    }
+
+    pub fn trigger(&mut self) {
+        self.volume = self.initial_volume;
+        self.timer = self.period;
+    }
+
+    pub fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = self.period;
+        if self.increase && self.volume < 15 {
+            self.volume += 1;
+        } else if !self.increase && self.volume > 0 {
+            self.volume -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triggered(value: u32) -> Envelope {
+        let mut envelope = Envelope::default();
+        envelope.write(value);
+        envelope.trigger();
+        envelope
+    }
+
+    #[test]
+    fn steps_every_period_ticks() {
+        // Volume 15, decreasing every 3 ticks
+        let mut envelope = triggered(0xf3);
+        let volumes: Vec<u32> = (0..7)
+            .map(|_| {
+                envelope.clock();
+                envelope.volume
+            })
+            .collect();
+        assert_eq!(volumes, vec![15, 15, 14, 14, 14, 13, 13]);
+    }
+
+    #[test]
+    fn stops_at_the_volume_limits() {
+        let mut envelope = triggered(0xe9);
+        for _ in 0..4 {
+            envelope.clock();
+        }
+        assert_eq!(envelope.volume, 15);
+
+        let mut envelope = triggered(0x11);
+        for _ in 0..4 {
+            envelope.clock();
+        }
+        assert_eq!(envelope.volume, 0);
+    }
+
+    #[test]
+    fn a_period_of_zero_holds_the_volume() {
+        let mut envelope = triggered(0xa0);
+        for _ in 0..16 {
+            envelope.clock();
+        }
+        assert_eq!(envelope.volume, 10);
+    }
+}
@@ -0,0 +1,72 @@
+/// Silences a channel after a set time, when enabled. It counts down at 256 Hz.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LengthCounter {
+    counter: u32,
+    pub enabled: bool,
+}
+
+impl LengthCounter {
+    /// Loads the length field of a register, which counts up towards `max`.
+    pub fn load(&mut self, max: u32, length: u32) {
+        self.counter = max - length;
+    }
+
+    /// Restarting a channel whose length ran out gives it the full length.
+    pub fn trigger(&mut self, max: u32) {
+        if self.counter == 0 {
+            self.counter = max;
+        }
+    }
+
+    /// Returns true when the length just ran out, turning the channel off.
+    pub fn clock(&mut self) -> bool {
+        if !self.enabled || self.counter == 0 {
+            return false;
+        }
+        self.counter -= 1;
+        self.counter == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clocks_until_expired(length: &mut LengthCounter) -> u32 {
+        (1..=256).find(|_| length.clock()).unwrap()
+    }
+
+    #[test]
+    fn triggering_at_zero_reloads_the_full_length() {
+        let mut length = LengthCounter {
+            enabled: true,
+            ..Default::default()
+        };
+        length.trigger(64);
+        assert_eq!(clocks_until_expired(&mut length), 64);
+        // Once expired it stays quiet until triggered again
+        assert!(!length.clock());
+        length.trigger(256);
+        assert_eq!(clocks_until_expired(&mut length), 256);
+    }
+
+    #[test]
+    fn triggering_keeps_a_loaded_length() {
+        let mut length = LengthCounter {
+            enabled: true,
+            ..Default::default()
+        };
+        length.load(64, 60);
+        length.trigger(64);
+        assert_eq!(clocks_until_expired(&mut length), 4);
+    }
+
+    #[test]
+    fn disabled_counters_hold() {
+        let mut length = LengthCounter::default();
+        length.load(64, 63);
+        assert!(!length.clock());
+        length.enabled = true;
+        assert!(length.clock());
+    }
+}
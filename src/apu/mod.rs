@@ -0,0 +1,681 @@
+mod debug;
+mod direct_sound;
+mod envelope;
+mod length;
+mod noise;
+mod recording;
+mod square;
+mod wave;
+
+use crate::io::{
+    IoRegisters, IoWrite, FIFO_A, FIFO_B, SOUND1CNT_H, SOUND1CNT_L, SOUND1CNT_X, SOUND2CNT_H,
+    SOUND2CNT_L, SOUND3CNT_H, SOUND3CNT_L, SOUND3CNT_X, SOUND4CNT_H, SOUND4CNT_L, SOUNDBIAS,
+    SOUNDCNT_H, SOUNDCNT_L, SOUNDCNT_X, WAVE_RAM,
+};
+use crate::memory::Memory;
+use crate::timer::TimerRate;
+use crate::util::get_bits;
+use debug::Scopes;
+pub use debug::{ChannelState, ChannelVolume, Frequency, SoundDebug};
+use direct_sound::DirectSound;
+use noise::Noise;
+use recording::Recorder;
+pub use recording::Recording;
+use square::Square;
+use wave::{Wave, WAVE_BANK_SIZE};
+
+pub const NUM_CHANNELS: usize = 6;
+pub const CHANNEL_NAMES: [&str; NUM_CHANNELS] =
+    ["Square 1", "Square 2", "Wave", "Noise", "FIFO A", "FIFO B"];
+
+/// The frame sequencer ticks at 512 Hz, every 32768 CPU cycles.
+const SEQUENCER_PERIOD: u32 = 1 << 15;
+
+const SOUNDCNT_X_MASTER: u32 = 1 << 7;
+/// The restart bit of the frequency registers, which isn't stored.
+const RESTART: u32 = 1 << 15;
+/// SOUNDCNT_H bits of FIFO A; those of FIFO B are 4 bits higher (or 1 for the volume).
+const SOUNDCNT_H_FIFO_A_FULL_VOLUME: u32 = 1 << 2;
+const SOUNDCNT_H_FIFO_A_RIGHT: u32 = 1 << 8;
+const SOUNDCNT_H_FIFO_A_LEFT: u32 = 1 << 9;
+const SOUNDCNT_H_FIFO_A_TIMER: u32 = 1 << 10;
+const SOUNDCNT_H_FIFO_A_RESET: u32 = 1 << 11;
+
+/// The output is a 10-bit level, before being cut down to the resolution set in SOUNDBIAS.
+const MAX_LEVEL: i32 = 0x3ff;
+/// Scales the output level, less the bias, to 16 bits.
+const SAMPLE_SCALE: i32 = 32;
+
+/// The output is sampled every 512 CPU cycles at the lowest SOUNDBIAS setting (32768 Hz),
+///   and twice as often for each setting above it.
+pub const CPU_CLOCK_HZ: u32 = 1 << 24;
+const BASE_SAMPLE_CYCLES: u32 = 512;
+/// Samples kept when nobody collects them, one second's worth at the highest rate.
+const MAX_QUEUED_SAMPLES: usize = (CPU_CLOCK_HZ / (BASE_SAMPLE_CYCLES >> 3)) as usize;
+
+/// The registers of the four PSG channels, which are cleared and ignore writes while the
+///   sound circuits are off.
+const PSG_REGISTERS: [u32; 11] = [
+    SOUND1CNT_L,
+    SOUND1CNT_H,
+    SOUND1CNT_X,
+    SOUND2CNT_L,
+    SOUND2CNT_H,
+    SOUND3CNT_L,
+    SOUND3CNT_H,
+    SOUND3CNT_X,
+    SOUND4CNT_L,
+    SOUND4CNT_H,
+    SOUNDCNT_L,
+];
+
+/// The sound controller: the four programmable sound generator channels inherited from the
+///   Game Boy, clocked by the 512 Hz frame sequencer (lengths count down at 256 Hz, the
+///   sweep steps at 128 Hz and envelopes at 64 Hz), plus the two Direct Sound FIFOs.
+pub struct Apu {
+    // CPU cycles left until the next frame sequencer tick
+    sequencer_timer: u32,
+    sequencer_step: u32,
+    // CPU cycles counted towards the next output sample
+    sample_cycles: u32,
+    samples: Vec<[i16; 2]>,
+    recorder: Option<Recorder>,
+    finished_recording: Option<Recording>,
+    scopes: Scopes,
+    pub debug: SoundDebug,
+    pub square1: Square,
+    pub square2: Square,
+    pub wave: Wave,
+    pub noise: Noise,
+    pub fifo_a: DirectSound,
+    pub fifo_b: DirectSound,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self {
+            sequencer_timer: SEQUENCER_PERIOD,
+            sequencer_step: 0,
+            sample_cycles: 0,
+            samples: Vec::new(),
+            recorder: None,
+            finished_recording: None,
+            scopes: Scopes::default(),
+            debug: SoundDebug::default(),
+            square1: Square::with_sweep(),
+            square2: Square::default(),
+            wave: Wave::default(),
+            noise: Noise::default(),
+            fifo_a: DirectSound::default(),
+            fifo_b: DirectSound::default(),
+        }
+    }
+}
+
+impl Apu {
+    pub fn register_io_hooks(io: &mut IoRegisters<Memory>) {
+        // The BIOS centers the output at boot
+        io.set(SOUNDBIAS, 0x200);
+
+        io.on_write(SOUND1CNT_L, |memory, write| {
+            psg_write(memory, write, |memory, value| {
+                memory.apu.square1.write_sweep(value)
+            })
+        });
+        io.on_write(SOUND1CNT_H, |memory, write| {
+            psg_write(memory, write, |memory, value| {
+                memory.apu.square1.write_control(value)
+            })
+        });
+        io.on_write(SOUND1CNT_X, |memory, write| {
+            psg_write(memory, write, |memory, value| {
+                memory.apu.square1.write_frequency(value)
+            }) & !RESTART
+        });
+        io.on_write(SOUND2CNT_L, |memory, write| {
+            psg_write(memory, write, |memory, value| {
+                memory.apu.square2.write_control(value)
+            })
+        });
+        io.on_write(SOUND2CNT_H, |memory, write| {
+            psg_write(memory, write, |memory, value| {
+                memory.apu.square2.write_frequency(value)
+            }) & !RESTART
+        });
+
+        // Switching banks swaps which one the WAVE_RAM registers show
+        io.on_write(SOUND3CNT_L, |memory, write| {
+            psg_write(memory, write, |memory, value| {
+                memory.apu.wave.sync_cpu_bank(read_wave_ram(&memory.io));
+                if memory.apu.wave.write_select(value) {
+                    let wave = &memory.apu.wave;
+                    write_wave_ram(&mut memory.io, &wave.banks[wave.cpu_bank()]);
+                }
+            })
+        });
+        io.on_write(SOUND3CNT_H, |memory, write| {
+            psg_write(memory, write, |memory, value| {
+                memory.apu.wave.write_control(value)
+            })
+        });
+        io.on_write(SOUND3CNT_X, |memory, write| {
+            psg_write(memory, write, |memory, value| {
+                memory.apu.wave.write_frequency(value)
+            }) & !RESTART
+        });
+        io.on_write(SOUND4CNT_L, |memory, write| {
+            psg_write(memory, write, |memory, value| {
+                memory.apu.noise.write_control(value)
+            })
+        });
+        io.on_write(SOUND4CNT_H, |memory, write| {
+            psg_write(memory, write, |memory, value| {
+                memory.apu.noise.write_frequency(value)
+            }) & !RESTART
+        });
+        io.on_write(SOUNDCNT_L, |memory, write| {
+            psg_write(memory, write, |_, _| ())
+        });
+
+        io.on_write(SOUNDCNT_X, |memory, write| {
+            let enabled = write.value & SOUNDCNT_X_MASTER != 0;
+            if enabled && write.old & SOUNDCNT_X_MASTER == 0 {
+                memory.apu.sequencer_step = 0;
+            } else if !enabled {
+                memory.apu.power_off(&mut memory.io);
+            }
+            write.value & SOUNDCNT_X_MASTER
+        });
+        // The reset bits empty the FIFOs without being stored
+        io.on_write(SOUNDCNT_H, |memory, write| {
+            if write.value & SOUNDCNT_H_FIFO_A_RESET != 0 {
+                memory.apu.fifo_a.reset();
+            }
+            if write.value & (SOUNDCNT_H_FIFO_A_RESET << 4) != 0 {
+                memory.apu.fifo_b.reset();
+            }
+            write.value & !(SOUNDCNT_H_FIFO_A_RESET | (SOUNDCNT_H_FIFO_A_RESET << 4))
+        });
+        io.on_write(FIFO_A, |memory, write| {
+            push_samples(&mut memory.apu.fifo_a, write);
+            0
+        });
+        io.on_write(FIFO_B, |memory, write| {
+            push_samples(&mut memory.apu.fifo_b, write);
+            0
+        });
+
+        // The low bits report which channels are playing
+        io.on_read(SOUNDCNT_X, |memory| {
+            memory.io.get(SOUNDCNT_X) | memory.apu.channel_status()
+        });
+    }
+
+    /// Silences every channel and clears the PSG registers. Wave RAM keeps its contents, and
+    ///   the output keeps being sampled, recorded and debugged.
+    fn power_off(&mut self, io: &mut IoRegisters<Memory>) {
+        self.wave.sync_cpu_bank(read_wave_ram(io));
+        let mut previous = std::mem::take(self);
+        self.wave.banks = previous.wave.banks;
+        self.sample_cycles = previous.sample_cycles;
+        self.samples = std::mem::take(&mut previous.samples);
+        self.recorder = previous.recorder.take();
+        self.finished_recording = previous.finished_recording.take();
+        self.scopes = std::mem::take(&mut previous.scopes);
+        self.debug = previous.debug;
+
+        for register in PSG_REGISTERS {
+            io.set(register, 0);
+        }
+        write_wave_ram(io, &self.wave.banks[self.wave.cpu_bank()]);
+    }
+
+    /// Bits 0-3 of SOUNDCNT_X: whether each channel is on.
+    pub fn channel_status(&self) -> u32 {
+        [
+            self.square1.enabled,
+            self.square2.enabled,
+            self.wave.enabled,
+            self.noise.enabled,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |status, (channel, enabled)| {
+            status | ((*enabled as u32) << channel)
+        })
+    }
+
+    /// The rate the output is sampled at, set by SOUNDBIAS.
+    pub fn sample_rate<C>(io: &IoRegisters<C>) -> u32 {
+        CPU_CLOCK_HZ / Self::sample_period(io)
+    }
+
+    fn sample_period<C>(io: &IoRegisters<C>) -> u32 {
+        BASE_SAMPLE_CYCLES >> get_bits(io.get(SOUNDBIAS), 14, 2)
+    }
+
+    /// Hands over the left and right samples produced since the last call.
+    pub fn take_samples(&mut self) -> Vec<[i16; 2]> {
+        std::mem::take(&mut self.samples)
+    }
+
+    /// Starts recording at the next frame boundary, optionally with each channel apart.
+    pub fn start_recording(&mut self, separate_channels: bool) {
+        if self.recorder.is_none() {
+            self.recorder = Some(Recorder::Starting { separate_channels });
+        }
+    }
+
+    /// Stops recording at the next frame boundary, after which `take_recording` has it.
+    ///   Without `at_frame_boundary`, the recording finishes straight away instead.
+    pub fn stop_recording(&mut self, at_frame_boundary: bool) {
+        if !at_frame_boundary {
+            // A recording that hasn't started yet is simply dropped
+            self.finished_recording = match self.recorder.take() {
+                Some(Recorder::Recording(recording) | Recorder::Stopping(recording)) => {
+                    Some(recording)
+                }
+                Some(Recorder::Starting { .. }) | None => self.finished_recording.take(),
+            };
+            return;
+        }
+        self.recorder = match self.recorder.take() {
+            Some(Recorder::Recording(recording)) => Some(Recorder::Stopping(recording)),
+            // A recording that hasn't started yet is simply dropped
+            Some(Recorder::Starting { .. }) | None => None,
+            stopping => stopping,
+        };
+    }
+
+    /// Whether a recording is running, or about to start or stop.
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    pub fn take_recording(&mut self) -> Option<Recording> {
+        self.finished_recording.take()
+    }
+
+    /// Called as V-Blank starts, once the samples before it have been taken.
+    fn frame_boundary<C>(&mut self, io: &IoRegisters<C>) {
+        if let Some(recorder) = self.recorder.take() {
+            let (recorder, finished) = recorder.frame_boundary(Self::sample_rate(io));
+            self.recorder = recorder;
+            if finished.is_some() {
+                self.finished_recording = finished;
+            }
+        }
+    }
+
+    /// Runs the channels for `cycles`. `vblank` is how far into those cycles V-Blank
+    ///   started, if it did, so recordings start and stop on the exact sample.
+    pub fn step<C>(&mut self, io: &IoRegisters<C>, cycles: usize, vblank: Option<usize>) {
+        match vblank {
+            Some(offset) => {
+                self.collect_samples(io, offset as u32);
+                self.frame_boundary(io);
+                self.collect_samples(io, (cycles - offset) as u32);
+            }
+            None => self.collect_samples(io, cycles as u32),
+        }
+        if io.get(SOUNDCNT_X) & SOUNDCNT_X_MASTER == 0 {
+            return;
+        }
+        if self.wave.plays_cpu_bank() {
+            self.wave.sync_cpu_bank(read_wave_ram(io));
+        }
+
+        let mut cycles = cycles as u32;
+        self.square1.step(cycles);
+        self.square2.step(cycles);
+        self.wave.step(cycles);
+        self.noise.step(cycles);
+
+        while cycles >= self.sequencer_timer {
+            cycles -= self.sequencer_timer;
+            self.sequencer_timer = SEQUENCER_PERIOD;
+            self.clock_sequencer();
+        }
+        self.sequencer_timer -= cycles;
+    }
+
+    /// Plays the next sample of the FIFOs paced by `timer`, returning whether FIFO A and
+    ///   FIFO B want a refill.
+    pub fn timer_overflow<C>(&mut self, io: &IoRegisters<C>, timer: usize) -> [bool; 2] {
+        if io.get(SOUNDCNT_X) & SOUNDCNT_X_MASTER == 0 {
+            return [false; 2];
+        }
+        let soundcnt_h = io.get(SOUNDCNT_H);
+        let selects =
+            |shift: u32| (soundcnt_h & (SOUNDCNT_H_FIFO_A_TIMER << shift) != 0) as usize == timer;
+        [
+            selects(0) && self.fifo_a.timer_overflow(),
+            selects(4) && self.fifo_b.timer_overflow(),
+        ]
+    }
+
+    /// Samples the output every sample period, taking out the bias so silence is 0. The
+    ///   samples describe the state at the start of this step, which is close enough.
+    fn collect_samples<C>(&mut self, io: &IoRegisters<C>, cycles: u32) {
+        let period = Self::sample_period(io);
+        self.sample_cycles += cycles;
+        while self.sample_cycles >= period {
+            self.sample_cycles -= period;
+            let (sample, channels) = if io.get(SOUNDCNT_X) & SOUNDCNT_X_MASTER != 0 {
+                let levels = self.channel_levels(io);
+                let mut audible = levels;
+                for (channel, level) in audible.iter_mut().enumerate() {
+                    if !self.debug.audible(channel) {
+                        *level = [0; 2];
+                    }
+                }
+                let bias = get_bits(io.get(SOUNDBIAS), 0, 10) as i32;
+                let sample = mix(io, &audible).map(|level| to_sample(level as i32 - bias));
+                (sample, levels.map(|level| level.map(to_sample)))
+            } else {
+                ([0; 2], [[0; 2]; NUM_CHANNELS])
+            };
+            self.scopes.push(self.raw_outputs());
+
+            if let Some(recording) = self.recorder.as_mut().and_then(Recorder::active) {
+                recording.push(sample, &channels);
+            }
+            if self.samples.len() < MAX_QUEUED_SAMPLES {
+                self.samples.push(sample);
+            }
+        }
+    }
+
+    fn clock_sequencer(&mut self) {
+        if self.sequencer_step % 2 == 0 {
+            self.square1.clock_length();
+            self.square2.clock_length();
+            self.wave.clock_length();
+            self.noise.clock_length();
+        }
+        if self.sequencer_step % 4 == 2 {
+            self.square1.clock_sweep();
+        }
+        if self.sequencer_step == 7 {
+            self.square1.clock_envelope();
+            self.square2.clock_envelope();
+            self.noise.clock_envelope();
+        }
+        self.sequencer_step = (self.sequencer_step + 1) % 8;
+    }
+
+    /// Each channel's output before routing and volume, scaled to -1 to 1.
+    fn raw_outputs(&self) -> [f32; NUM_CHANNELS] {
+        [
+            self.square1.output() as f32 / 15.0,
+            self.square2.output() as f32 / 15.0,
+            self.wave.output() as f32 / 15.0,
+            self.noise.output() as f32 / 15.0,
+            self.fifo_a.sample as f32 / 128.0,
+            self.fifo_b.sample as f32 / 128.0,
+        ]
+    }
+
+    /// The oscilloscope samples of `channel`, oldest first, one per output sample.
+    pub fn scope(&self, channel: usize) -> Vec<f32> {
+        self.scopes.channel(channel)
+    }
+
+    /// The state of each channel, in `CHANNEL_NAMES` order. `timer_rates` are the overflow
+    ///   rates of timers 0 and 1, which set the FIFOs' sample rates.
+    pub fn channel_states<C>(
+        &self,
+        io: &IoRegisters<C>,
+        timer_rates: [TimerRate; 2],
+    ) -> [ChannelState; NUM_CHANNELS] {
+        let envelope = |envelope: &envelope::Envelope| ChannelVolume::Envelope {
+            volume: envelope.volume,
+            increase: envelope.increase,
+            period: envelope.period,
+        };
+        let soundcnt_h = io.get(SOUNDCNT_H);
+        let fifo = |fifo: &DirectSound, index: usize| {
+            let shift = index as u32 * 4;
+            let routing = (SOUNDCNT_H_FIFO_A_LEFT | SOUNDCNT_H_FIFO_A_RIGHT) << shift;
+            let timer = (soundcnt_h & (SOUNDCNT_H_FIFO_A_TIMER << shift) != 0) as usize;
+            let full = soundcnt_h & (SOUNDCNT_H_FIFO_A_FULL_VOLUME << index) != 0;
+            ChannelState {
+                playing: fifo.len() > 0 && soundcnt_h & routing != 0,
+                frequency: timer_rates[timer].into(),
+                tonal: false,
+                volume: ChannelVolume::Fifo {
+                    percent: if full { 100 } else { 50 },
+                    queued: fifo.len(),
+                },
+            }
+        };
+
+        [
+            ChannelState {
+                playing: self.square1.enabled,
+                frequency: Frequency::Hz(self.square1.frequency_hz()),
+                tonal: true,
+                volume: envelope(&self.square1.envelope),
+            },
+            ChannelState {
+                playing: self.square2.enabled,
+                frequency: Frequency::Hz(self.square2.frequency_hz()),
+                tonal: true,
+                volume: envelope(&self.square2.envelope),
+            },
+            ChannelState {
+                playing: self.wave.enabled,
+                frequency: Frequency::Hz(self.wave.frequency_hz()),
+                tonal: true,
+                volume: ChannelVolume::Percent(self.wave.volume_percent()),
+            },
+            ChannelState {
+                playing: self.noise.enabled,
+                frequency: Frequency::Hz(self.noise.frequency_hz()),
+                tonal: false,
+                volume: envelope(&self.noise.envelope),
+            },
+            fifo(&self.fifo_a, 0),
+            fifo(&self.fifo_b, 1),
+        ]
+    }
+
+    /// Both banks of wave RAM, including the CPU's latest writes to the one it sees.
+    pub fn wave_banks<C>(&self, io: &IoRegisters<C>) -> [[u8; WAVE_BANK_SIZE]; 2] {
+        let mut banks = self.wave.banks;
+        banks[self.wave.cpu_bank()] = read_wave_ram(io);
+        banks
+    }
+
+    /// Replaces a bank of wave RAM, as the debugger's editor does.
+    pub fn write_wave_bank<C>(
+        &mut self,
+        io: &mut IoRegisters<C>,
+        bank: usize,
+        bytes: [u8; WAVE_BANK_SIZE],
+    ) {
+        self.wave.banks[bank] = bytes;
+        if bank == self.wave.cpu_bank() {
+            write_wave_ram(io, &bytes);
+        }
+    }
+
+    /// Each channel's left and right contribution to the output, in `CHANNEL_NAMES` order.
+    ///   The PSG channels go through the SOUNDCNT_L routing and master volume and the
+    ///   SOUNDCNT_H PSG volume, the FIFOs through their SOUNDCNT_H routing and volume.
+    fn channel_levels<C>(&self, io: &IoRegisters<C>) -> [[i32; 2]; NUM_CHANNELS] {
+        let soundcnt_l = io.get(SOUNDCNT_L);
+        let soundcnt_h = io.get(SOUNDCNT_H);
+        // 25%, 50% or 100%; the fourth setting is prohibited
+        let psg_shift = 2 - get_bits(soundcnt_h, 0, 2).min(2);
+        let psg = [
+            self.square1.output(),
+            self.square2.output(),
+            self.wave.output(),
+            self.noise.output(),
+        ];
+
+        let mut levels = [[0; 2]; NUM_CHANNELS];
+        for (channel, output) in psg.into_iter().enumerate() {
+            // SOUNDCNT_L holds the right side's settings in its low bits
+            levels[channel] = [4, 0].map(|offset| {
+                let volume = get_bits(soundcnt_l, offset, 3) as i32 + 1;
+                let enabled = get_bits(soundcnt_l, offset + 8 + channel as u8, 1) != 0;
+                if enabled {
+                    (output * volume) >> psg_shift
+                } else {
+                    0
+                }
+            });
+        }
+        for (index, fifo) in [&self.fifo_a, &self.fifo_b].into_iter().enumerate() {
+            // 50% or 100% volume
+            let full = soundcnt_h & (SOUNDCNT_H_FIFO_A_FULL_VOLUME << index) != 0;
+            let level = fifo.sample as i32 * if full { 4 } else { 2 };
+            levels[4 + index] = [SOUNDCNT_H_FIFO_A_LEFT, SOUNDCNT_H_FIFO_A_RIGHT]
+                .map(|side| (soundcnt_h & (side << (index * 4)) != 0) as i32 * level);
+        }
+        levels
+    }
+}
+
+/// The left and right output levels: the channels added to the SOUNDBIAS level, clamped
+///   to 10 bits and cut down to the selected resolution.
+fn mix<C>(io: &IoRegisters<C>, levels: &[[i32; 2]; NUM_CHANNELS]) -> [u16; 2] {
+    let soundbias = io.get(SOUNDBIAS);
+    let bias = get_bits(soundbias, 0, 10) as i32;
+    // 9 bits at the lowest setting, down to 6 bits
+    let dropped_bits = 1 + get_bits(soundbias, 14, 2);
+
+    [0, 1].map(|side| {
+        let sum: i32 = levels.iter().map(|level| level[side]).sum();
+        let clamped = (sum + bias).clamp(0, MAX_LEVEL) as u16;
+        (clamped >> dropped_bits) << dropped_bits
+    })
+}
+
+/// Scales an output level, centered on 0, to a 16-bit sample.
+fn to_sample(level: i32) -> i16 {
+    (level * SAMPLE_SCALE).clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+/// Queues the bytes a write to FIFO_A or FIFO_B covers, lowest first.
+fn push_samples(fifo: &mut DirectSound, write: IoWrite) {
+    for byte in 0..4 {
+        if write.lanes & (0xff << (byte * 8)) != 0 {
+            fifo.push((write.value >> (byte * 8)) as i8);
+        }
+    }
+}
+
+/// Passes a write to a PSG register on to `apply`, unless the sound circuits are off.
+fn psg_write(memory: &mut Memory, write: IoWrite, apply: fn(&mut Memory, u32)) -> u32 {
+    if memory.io.get(SOUNDCNT_X) & SOUNDCNT_X_MASTER == 0 {
+        return write.old;
+    }
+    apply(memory, write.value);
+    write.value
+}
+
+fn read_wave_ram<C>(io: &IoRegisters<C>) -> [u8; WAVE_BANK_SIZE] {
+    let mut bytes = [0u8; WAVE_BANK_SIZE];
+    for (index, pair) in bytes.chunks_mut(2).enumerate() {
+        let halfword = io.get(WAVE_RAM + 2 * index as u32) as u16;
+        pair.copy_from_slice(&halfword.to_le_bytes());
+    }
+    bytes
+}
+
+fn write_wave_ram<C>(io: &mut IoRegisters<C>, bytes: &[u8; WAVE_BANK_SIZE]) {
+    for (index, pair) in bytes.chunks(2).enumerate() {
+        io.set(
+            WAVE_RAM + 2 * index as u32,
+            u16::from_le_bytes([pair[0], pair[1]]) as u32,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io;
+    use crate::memory::Memory;
+
+    #[test]
+    fn recordings_start_on_the_vblank_cycle() {
+        let io = IoRegisters::<Memory>::default();
+        let mut apu = Apu::default();
+        apu.start_recording(false);
+        // V-Blank 600 cycles in: the sample due at 512 comes before it
+        apu.step(&io, 1000, Some(600));
+        assert!(apu.take_recording().is_none());
+        // The next sample is due at 1024
+        apu.step(&io, 23, None);
+        apu.step(&io, 1, None);
+
+        apu.stop_recording(false);
+        assert!(!apu.is_recording());
+        let recording = apu.take_recording().unwrap();
+        assert_eq!(recording.mixed.len(), 1);
+        assert_eq!(apu.take_samples().len(), 2);
+    }
+
+    #[test]
+    fn recordings_stop_on_the_vblank_cycle() {
+        let io = IoRegisters::<Memory>::default();
+        let mut apu = Apu::default();
+        apu.start_recording(false);
+        apu.step(&io, 0, Some(0));
+        apu.stop_recording(true);
+        assert!(apu.is_recording());
+        // The sample due at 512 is the last before V-Blank, the one at 1024 is after it
+        apu.step(&io, 1024, Some(1000));
+        assert!(!apu.is_recording());
+        assert_eq!(apu.take_recording().unwrap().mixed.len(), 1);
+    }
+
+    const SOUND2_ENABLED: u32 = 1 << 1;
+
+    fn sound_status(memory: &Memory) -> u32 {
+        io::read(memory, SOUNDCNT_X, 2) & 0xf
+    }
+
+    #[test]
+    fn status_bits_clear_when_the_length_expires() {
+        let mut memory = Memory::default();
+        io::write(&mut memory, SOUNDCNT_X, SOUNDCNT_X_MASTER, 2);
+        // Full volume with a length of 1, triggered with the length enabled
+        io::write(&mut memory, SOUND2CNT_L, 0xf000 | 63, 2);
+        io::write(&mut memory, SOUND2CNT_H, RESTART | 0x4000 | 1000, 2);
+        assert_eq!(sound_status(&memory), SOUND2_ENABLED);
+
+        // The first sequencer step clocks the length counters
+        memory
+            .apu
+            .step(&memory.io, SEQUENCER_PERIOD as usize - 1, None);
+        assert_eq!(sound_status(&memory), SOUND2_ENABLED);
+        memory.apu.step(&memory.io, 1, None);
+        assert_eq!(sound_status(&memory), 0);
+    }
+
+    #[test]
+    fn psg_writes_are_ignored_while_the_master_enable_is_off() {
+        let mut memory = Memory::default();
+        io::write(&mut memory, SOUND2CNT_L, 0xf000, 2);
+        io::write(&mut memory, SOUND2CNT_H, RESTART | 1000, 2);
+        assert_eq!(memory.io.get(SOUND2CNT_L), 0);
+        assert_eq!(sound_status(&memory), 0);
+
+        // Powering on leaves the channels off until they are written again
+        io::write(&mut memory, SOUNDCNT_X, SOUNDCNT_X_MASTER, 2);
+        assert_eq!(sound_status(&memory), 0);
+        io::write(&mut memory, SOUND2CNT_L, 0xf000, 2);
+        io::write(&mut memory, SOUND2CNT_H, RESTART | 1000, 2);
+        assert_eq!(sound_status(&memory), SOUND2_ENABLED);
+
+        // Powering off silences them and clears their registers
+        io::write(&mut memory, SOUNDCNT_X, 0, 2);
+        assert_eq!(sound_status(&memory), 0);
+        assert_eq!(memory.io.get(SOUND2CNT_L), 0);
+    }
+}
@@ -0,0 +1,108 @@
+use super::envelope::Envelope;
+use super::length::LengthCounter;
+use crate::util::get_bits;
+
+const MAX_LENGTH: u32 = 64;
+
+/// Channel 4, which plays the output of a 15-bit linear feedback shift register, or a
+///   7-bit one for a more metallic sound.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Noise {
+    pub enabled: bool,
+    pub length: LengthCounter,
+    pub envelope: Envelope,
+    divisor_code: u32,
+    narrow: bool,
+    shift: u32,
+    // CPU cycles left until the next shift
+    timer: u32,
+    lfsr: u16,
+}
+
+impl Noise {
+    /// SOUND4CNT_L: length and envelope.
+    pub fn write_control(&mut self, value: u32) {
+        self.length.load(MAX_LENGTH, get_bits(value, 0, 6));
+        self.envelope.write(get_bits(value, 8, 8));
+        if !self.envelope.dac_enabled() {
+            self.enabled = false;
+        }
+    }
+
+    /// SOUND4CNT_H: shift frequency, register width, length enable and restart.
+    pub fn write_frequency(&mut self, value: u32) {
+        self.divisor_code = get_bits(value, 0, 3);
+        self.narrow = get_bits(value, 3, 1) != 0;
+        self.shift = get_bits(value, 4, 4);
+        self.length.enabled = get_bits(value, 14, 1) != 0;
+        if get_bits(value, 15, 1) != 0 {
+            self.trigger();
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger(MAX_LENGTH);
+        self.envelope.trigger();
+        self.timer = self.period();
+        self.lfsr = 0x7fff;
+    }
+
+    /// The register shifts every 64 CPU cycles per step of the divisor (32 for a divisor
+    ///   of 0), doubled for each step of the shift clock.
+    fn period(&self) -> u32 {
+        let divisor = if self.divisor_code == 0 {
+            32
+        } else {
+            self.divisor_code * 64
+        };
+        divisor << self.shift
+    }
+
+    /// How often the register shifts, in Hz.
+    pub fn frequency_hz(&self) -> f64 {
+        super::CPU_CLOCK_HZ as f64 / self.period() as f64
+    }
+
+    pub fn step(&mut self, mut cycles: u32) {
+        // Shift clocks 14 and 15 stop the register
+        if !self.enabled || self.shift >= 14 {
+            return;
+        }
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+
+            let feedback = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+            self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+            if self.narrow {
+                self.lfsr = (self.lfsr & !(1 << 6)) | (feedback << 6);
+            }
+        }
+        self.timer -= cycles;
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// The signal level, from -15 to 15. The channel is high while the register's low bit
+    ///   is clear.
+    pub fn output(&self) -> i32 {
+        if !self.enabled {
+            return 0;
+        }
+        let volume = self.envelope.volume as i32;
+        if self.lfsr & 1 == 0 {
+            volume
+        } else {
+            -volume
+        }
+    }
+}
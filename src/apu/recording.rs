@@ -0,0 +1,61 @@
+use super::NUM_CHANNELS;
+
+/// Output captured between two frame boundaries, at the sample rate in effect when the
+///   recording started. The rate is not followed if the game changes it mid-recording.
+pub struct Recording {
+    pub sample_rate: u32,
+    /// The left and right output, as played.
+    pub mixed: Vec<[i16; 2]>,
+    /// Each channel's left and right contribution to the output, if asked for.
+    pub channels: Option<Vec<[[i16; 2]; NUM_CHANNELS]>>,
+}
+
+impl Recording {
+    fn new(sample_rate: u32, separate_channels: bool) -> Self {
+        Self {
+            sample_rate,
+            mixed: Vec::new(),
+            channels: separate_channels.then(Vec::new),
+        }
+    }
+
+    pub fn push(&mut self, mixed: [i16; 2], channels: &[[i16; 2]; NUM_CHANNELS]) {
+        self.mixed.push(mixed);
+        if let Some(samples) = &mut self.channels {
+            samples.push(*channels);
+        }
+    }
+}
+
+/// Recordings start and stop at the start of V-Blank, the same point frames are counted
+///   at, so they line up exactly with the frames shown meanwhile.
+pub enum Recorder {
+    Starting { separate_channels: bool },
+    Recording(Recording),
+    Stopping(Recording),
+}
+
+impl Recorder {
+    /// Moves on at a frame boundary, returning the finished recording when one stops.
+    pub fn frame_boundary(self, sample_rate: u32) -> (Option<Self>, Option<Recording>) {
+        match self {
+            Self::Starting { separate_channels } => (
+                Some(Self::Recording(Recording::new(
+                    sample_rate,
+                    separate_channels,
+                ))),
+                None,
+            ),
+            Self::Recording(recording) => (Some(Self::Recording(recording)), None),
+            Self::Stopping(recording) => (None, Some(recording)),
+        }
+    }
+
+    /// The recording samples go to right now, if any.
+    pub fn active(&mut self) -> Option<&mut Recording> {
+        match self {
+            Self::Starting { .. } => None,
+            Self::Recording(recording) | Self::Stopping(recording) => Some(recording),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use super::envelope::Envelope;
+use super::length::LengthCounter;
+use crate::util::get_bits;
+
+const MAX_LENGTH: u32 = 64;
+
+/// High and low steps of the 12.5%, 25%, 50% and 75% duty cycles.
+const DUTY_PATTERNS: [[bool; 8]; 4] = [
+    [false, false, false, false, false, false, false, true],
+    [true, false, false, false, false, false, false, true],
+    [true, false, false, false, false, true, true, true],
+    [false, true, true, true, true, true, true, false],
+];
+
+/// Channel 1's frequency sweep, which moves the frequency up or down every `period` 128 Hz
+///   ticks.
+#[derive(Debug, Default, Clone, Copy)]
+struct Sweep {
+    period: u32,
+    decrease: bool,
+    shift: u32,
+    timer: u32,
+    enabled: bool,
+    // The frequency the sweep works from
+    shadow: u32,
+}
+
+impl Sweep {
+    fn next_frequency(&self) -> u32 {
+        let delta = self.shadow >> self.shift;
+        if self.decrease {
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        }
+    }
+
+    /// A period of 0 counts as 8 for the timer, though it stops the sweep.
+    fn reload_timer(&mut self) {
+        self.timer = if self.period == 0 { 8 } else { self.period };
+    }
+}
+
+/// A square wave channel: channel 1, with its frequency sweep, or channel 2.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Square {
+    pub enabled: bool,
+    pub length: LengthCounter,
+    pub envelope: Envelope,
+    sweep: Option<Sweep>,
+    duty: usize,
+    pub frequency: u32,
+    // CPU cycles left until the next duty step
+    timer: u32,
+    step: usize,
+}
+
+impl Square {
+    pub fn with_sweep() -> Self {
+        Self {
+            sweep: Some(Sweep::default()),
+            ..Default::default()
+        }
+    }
+
+    /// SOUND1CNT_L.
+    pub fn write_sweep(&mut self, value: u32) {
+        if let Some(sweep) = &mut self.sweep {
+            sweep.shift = get_bits(value, 0, 3);
+            sweep.decrease = get_bits(value, 3, 1) != 0;
+            sweep.period = get_bits(value, 4, 3);
+        }
+    }
+
+    /// SOUND1CNT_H or SOUND2CNT_L: length, duty cycle and envelope.
+    pub fn write_control(&mut self, value: u32) {
+        self.length.load(MAX_LENGTH, get_bits(value, 0, 6));
+        self.duty = get_bits(value, 6, 2) as usize;
+        self.envelope.write(get_bits(value, 8, 8));
+        if !self.envelope.dac_enabled() {
+            self.enabled = false;
+        }
+    }
+
+    /// SOUND1CNT_X or SOUND2CNT_H: frequency, length enable and restart.
+    pub fn write_frequency(&mut self, value: u32) {
+        self.frequency = get_bits(value, 0, 11);
+        self.length.enabled = get_bits(value, 14, 1) != 0;
+        if get_bits(value, 15, 1) != 0 {
+            self.trigger();
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger(MAX_LENGTH);
+        self.envelope.trigger();
+        self.timer = self.period();
+
+        if let Some(sweep) = &mut self.sweep {
+            sweep.shadow = self.frequency;
+            sweep.reload_timer();
+            sweep.enabled = sweep.period != 0 || sweep.shift != 0;
+            // An overflowing sweep silences the channel straight away
+            if sweep.shift != 0 && sweep.next_frequency() > 2047 {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// Each duty step lasts 16 CPU cycles for every step the frequency is below 2048.
+    fn period(&self) -> u32 {
+        (2048 - self.frequency) * 16
+    }
+
+    /// The pitch in Hz, one cycle being 8 duty steps.
+    pub fn frequency_hz(&self) -> f64 {
+        super::CPU_CLOCK_HZ as f64 / (self.period() * 8) as f64
+    }
+
+    pub fn step(&mut self, mut cycles: u32) {
+        if !self.enabled {
+            return;
+        }
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.step = (self.step + 1) % 8;
+        }
+        self.timer -= cycles;
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_sweep(&mut self) {
+        let Some(sweep) = &mut self.sweep else {
+            return;
+        };
+        sweep.timer = sweep.timer.saturating_sub(1);
+        if sweep.timer > 0 {
+            return;
+        }
+        sweep.reload_timer();
+        if !sweep.enabled || sweep.period == 0 {
+            return;
+        }
+
+        let frequency = sweep.next_frequency();
+        if frequency > 2047 {
+            self.enabled = false;
+        } else if sweep.shift != 0 {
+            sweep.shadow = frequency;
+            self.frequency = frequency;
+            // The new frequency is checked for overflow once more, without being applied
+            if sweep.next_frequency() > 2047 {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// The signal level, from -15 to 15.
+    pub fn output(&self) -> i32 {
+        if !self.enabled {
+            return 0;
+        }
+        let volume = self.envelope.volume as i32;
+        if DUTY_PATTERNS[self.duty][self.step] {
+            volume
+        } else {
+            -volume
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESTART: u32 = 1 << 15;
+
+    /// Channel 1 at full volume, triggered at `frequency` with the given sweep settings.
+    fn sweeping(sweep: u32, frequency: u32) -> Square {
+        let mut square = Square::with_sweep();
+        square.write_sweep(sweep);
+        square.write_control(0xf000);
+        square.write_frequency(RESTART | frequency);
+        square
+    }
+
+    #[test]
+    fn an_overflowing_sweep_disables_the_channel_on_trigger() {
+        // Period 1, increasing with a shift of 1: 1500 + 750 is past 2047
+        let square = sweeping(0x11, 1500);
+        assert!(!square.enabled);
+
+        // The same frequency is fine without a shift to apply
+        let square = sweeping(0x10, 1500);
+        assert!(square.enabled);
+    }
+
+    #[test]
+    fn the_sweep_checks_the_frequency_it_moves_to() {
+        // 1200 moves to 1800, which would overflow on its next step
+        let mut square = sweeping(0x11, 1200);
+        assert!(square.enabled);
+        square.clock_sweep();
+        assert_eq!(square.frequency, 1800);
+        assert!(!square.enabled);
+    }
+
+    #[test]
+    fn the_sweep_steps_every_period_ticks() {
+        // Period 2, increasing with a shift of 2
+        let mut square = sweeping(0x22, 1000);
+        square.clock_sweep();
+        assert_eq!(square.frequency, 1000);
+        square.clock_sweep();
+        assert_eq!(square.frequency, 1250);
+        assert!(square.enabled);
+
+        // Decreasing sweeps can't overflow
+        let mut square = sweeping(0x1f, 2000);
+        square.clock_sweep();
+        assert_eq!(square.frequency, 2000 - (2000 >> 7));
+        assert!(square.enabled);
+    }
+}
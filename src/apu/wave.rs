@@ -0,0 +1,148 @@
+use super::length::LengthCounter;
+use crate::util::get_bits;
+
+const MAX_LENGTH: u32 = 256;
+/// Each bank of wave RAM holds 32 4-bit samples.
+pub const WAVE_BANK_SIZE: usize = 16;
+
+/// Channel 3, which plays back 4-bit samples from wave RAM. There are two banks: the CPU
+///   reaches one through the WAVE_RAM registers while the other is played, and both can be
+///   played back to back as one 64 sample wave.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Wave {
+    pub enabled: bool,
+    pub length: LengthCounter,
+    dac_enabled: bool,
+    two_banks: bool,
+    /// The bank played first; the CPU sees the other one.
+    pub bank: usize,
+    volume_code: u32,
+    force_75: bool,
+    pub frequency: u32,
+    // CPU cycles left until the next sample
+    timer: u32,
+    // Samples into the wave, counting from the start of `bank`
+    position: usize,
+    sample: u8,
+    /// The contents of both banks. The one the CPU sees is only current as of the last
+    ///   call to `sync_cpu_bank`, since the CPU writes it through the IO registers.
+    pub banks: [[u8; WAVE_BANK_SIZE]; 2],
+}
+
+impl Wave {
+    /// The bank the CPU reads and writes through the WAVE_RAM registers.
+    pub fn cpu_bank(&self) -> usize {
+        self.bank ^ 1
+    }
+
+    /// SOUND3CNT_L. Returns whether the played bank changed, in which case the CPU's view
+    ///   of wave RAM must be swapped for the other bank.
+    pub fn write_select(&mut self, value: u32) -> bool {
+        let bank = get_bits(value, 6, 1) as usize;
+        self.two_banks = get_bits(value, 5, 1) != 0;
+        self.dac_enabled = get_bits(value, 7, 1) != 0;
+        if !self.dac_enabled {
+            self.enabled = false;
+        }
+
+        let swapped = bank != self.bank;
+        self.bank = bank;
+        swapped
+    }
+
+    /// SOUND3CNT_H: length and volume.
+    pub fn write_control(&mut self, value: u32) {
+        self.length.load(MAX_LENGTH, get_bits(value, 0, 8));
+        self.volume_code = get_bits(value, 13, 2);
+        self.force_75 = get_bits(value, 15, 1) != 0;
+    }
+
+    /// SOUND3CNT_X: frequency, length enable and restart.
+    pub fn write_frequency(&mut self, value: u32) {
+        self.frequency = get_bits(value, 0, 11);
+        self.length.enabled = get_bits(value, 14, 1) != 0;
+        if get_bits(value, 15, 1) != 0 {
+            self.trigger();
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger(MAX_LENGTH);
+        self.timer = self.period();
+        self.position = 0;
+    }
+
+    /// Each sample lasts 8 CPU cycles for every step the frequency is below 2048.
+    fn period(&self) -> u32 {
+        (2048 - self.frequency) * 8
+    }
+
+    /// Only a playing 64 sample wave reaches into the bank the CPU sees.
+    pub fn plays_cpu_bank(&self) -> bool {
+        self.enabled && self.two_banks
+    }
+
+    /// Copies the bank the CPU sees out of the WAVE_RAM registers.
+    pub fn sync_cpu_bank(&mut self, bytes: [u8; WAVE_BANK_SIZE]) {
+        let bank = self.cpu_bank();
+        self.banks[bank] = bytes;
+    }
+
+    /// The pitch in Hz, one cycle being the whole 32 or 64 sample wave.
+    pub fn frequency_hz(&self) -> f64 {
+        let wave_length = if self.two_banks { 64 } else { 32 };
+        super::CPU_CLOCK_HZ as f64 / (self.period() * wave_length) as f64
+    }
+
+    /// The output volume set by SOUND3CNT_H, in percent.
+    pub fn volume_percent(&self) -> u32 {
+        match (self.force_75, self.volume_code) {
+            (true, _) => 75,
+            (false, 0) => 0,
+            (false, code) => 100 >> (code - 1),
+        }
+    }
+
+    pub fn step(&mut self, mut cycles: u32) {
+        if !self.enabled {
+            return;
+        }
+        let wave_length = if self.two_banks { 64 } else { 32 };
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.position = (self.position + 1) % wave_length;
+
+            // The high nibble of each byte plays first
+            let bank = (self.bank + self.position / 32) % 2;
+            let byte = self.banks[bank][(self.position % 32) / 2];
+            self.sample = if self.position % 2 == 0 {
+                byte >> 4
+            } else {
+                byte & 0xf
+            };
+        }
+        self.timer -= cycles;
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    /// The signal level, from -15 to 15. Samples are centered before the volume shift, so
+    ///   quieter settings don't move the signal's midpoint.
+    pub fn output(&self) -> i32 {
+        if !self.enabled {
+            return 0;
+        }
+        let sample = self.sample as i32 * 2 - 15;
+        match (self.force_75, self.volume_code) {
+            (true, _) => sample * 3 / 4,
+            (false, 0) => 0,
+            (false, code) => sample >> (code - 1),
+        }
+    }
+}
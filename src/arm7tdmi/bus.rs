@@ -0,0 +1,46 @@
+#[allow(dead_code)] // Byte and halfword accesses arrive with the load/store instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessWidth {
+    Byte,
+    Halfword,
+    Word,
+}
+
+/// The SEQ hint the core puts on the bus: sequential accesses continue from the previous
+///   address, letting slow memories skip their setup time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessType {
+    NonSequential,
+    Sequential,
+}
+
+/// Everything the core sees of the system it sits in. Each access returns the cycles it
+///   took, so implementations decide the timing of their own memories.
+pub trait Bus {
+    /// An opcode fetch. Buses with a prefetcher can serve these differently from data reads.
+    fn fetch_word(&mut self, address: u32, access: AccessType) -> (u32, usize) {
+        self.read_word(address, access)
+    }
+
+    #[allow(dead_code)] // Unused until load instructions are implemented
+    fn read_byte(&mut self, address: u32, access: AccessType) -> (u8, usize);
+    #[allow(dead_code)] // Unused until load instructions are implemented
+    fn read_halfword(&mut self, address: u32, access: AccessType) -> (u16, usize);
+    fn read_word(&mut self, address: u32, access: AccessType) -> (u32, usize);
+
+    #[allow(dead_code)] // Unused until store instructions are implemented
+    fn write_byte(&mut self, address: u32, value: u8, access: AccessType) -> usize;
+    #[allow(dead_code)] // Unused until store instructions are implemented
+    fn write_halfword(&mut self, address: u32, value: u16, access: AccessType) -> usize;
+    #[allow(dead_code)] // Unused until store instructions are implemented
+    fn write_word(&mut self, address: u32, value: u32, access: AccessType) -> usize;
+
+    /// Whether an interrupt is being signalled on the core's IRQ input.
+    fn irq_line(&self) -> bool {
+        false
+    }
+
+    /// Cycles spent inside the core without touching the bus.
+    #[allow(dead_code)] // Unused until instructions with internal cycles are implemented
+    fn idle(&mut self, _cycles: usize) {}
+}
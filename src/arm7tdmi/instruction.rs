@@ -1,4 +1,4 @@
-use super::{memory::Memory, regfile::RegFile};
+use super::{bus::Bus, regfile::RegFile};
 use std::fmt;
 
 // TODO: Evaluate necessity of Copy
@@ -124,12 +124,16 @@ impl Instruction {
         }
     }
 
-    pub fn execute(
+    pub fn address(&self) -> u32 {
+        self.src_addr
+    }
+
+    pub fn execute<B: Bus>(
         &self,
         regfile: &mut RegFile,
-        memory: &mut Memory,
+        bus: &mut B,
     ) -> Result<bool, &'static str> {
-        self.inner_instr.execute(regfile, memory)
+        self.inner_instr.execute(regfile, bus)
     }
 }
 
@@ -162,7 +166,7 @@ enum InstrPayload {
 }
 
 impl InstrPayload {
-    fn execute(&self, regfile: &mut RegFile, _memory: &mut Memory) -> Result<bool, &'static str> {
+    fn execute<B: Bus>(&self, regfile: &mut RegFile, _bus: &mut B) -> Result<bool, &'static str> {
         match self {
             Self::Undefined => Err("Tried to execute undefined instruction"),
             Self::Branch { offset } => {
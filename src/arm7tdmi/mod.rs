@@ -1,26 +1,31 @@
+mod bus;
 mod instruction;
-mod memory;
 mod regfile;
 
+pub use bus::{AccessType, AccessWidth, Bus};
 use instruction::Instruction;
 
+/// The CPSR's I bit, which masks IRQs. The F bit below it masks FIQs.
+const CPSR_IRQ_DISABLE: u32 = 1 << 7;
+
 #[derive(Debug, Default)]
 pub enum OpMode {
     User,
     _Fiq,
     Supervisor,
     _Abort,
-    _Irq,
+    Irq,
     _System,
     #[default]
     Undefined,
 }
 
-pub struct Arm7TDMI {
+/// The CPU core, wired to the rest of the system through `B`.
+pub struct Arm7TDMI<B: Bus> {
     pub clock_cycle: usize,
     pub opmode: OpMode,
     pub regfile: regfile::RegFile,
-    pub memory: memory::Memory,
+    pub bus: B,
     pub is_idle: bool,
     pub fetch_addr: u32,
     pub fetch_instr: u32,
@@ -28,13 +33,13 @@ pub struct Arm7TDMI {
     pub exec_instr: Instruction,
 }
 
-impl Default for Arm7TDMI {
+impl<B: Bus + Default> Default for Arm7TDMI<B> {
     fn default() -> Self {
         let mut constructed_val = Self {
             clock_cycle: 0usize,
             opmode: OpMode::User,
             regfile: regfile::RegFile::default(),
-            memory: memory::Memory::default(),
+            bus: B::default(),
             is_idle: false,
             fetch_addr: 0u32,
             fetch_instr: 0xf0000000u32, // Default to UNPREDICTABLE
@@ -46,11 +51,7 @@ impl Default for Arm7TDMI {
     }
 }
 
-impl Arm7TDMI {
-    pub fn load_bios_rom(&mut self, bios_rom_bytes: &Vec<u8>) -> Result<(), &'static str> {
-        self.memory.load_bios_rom(bios_rom_bytes)
-    }
-
+impl<B: Bus> Arm7TDMI<B> {
     pub fn reset(&mut self) {
         // When the nRESET signal goes LOW a reset occurs, and the ARM7TDMI core
         //   abandons the executing instruction and continues to increment the address bus as if still
@@ -84,18 +85,30 @@ impl Arm7TDMI {
         self.is_idle = true;
     }
 
-    fn reload_pipeline(&mut self) {
+    /// Fetches an opcode, returning it along with the cycles the fetch took.
+    fn fetch_word(&mut self, address: u32, access: AccessType) -> (u32, usize) {
+        self.bus.fetch_word(address, access)
+    }
+
+    /// Refills the pipeline, returning the cycles spent: one non-sequential fetch
+    ///   followed by two sequential ones.
+    fn reload_pipeline(&mut self) -> usize {
         let cur_pc = self.regfile.get_register(15);
 
-        self.fetch_instr = self.memory.get_word((cur_pc.saturating_add(8)) as usize);
-        self.fetch_addr = cur_pc.saturating_add(8);
+        let (raw_exec_instr, exec_cycles) = self.fetch_word(cur_pc, AccessType::NonSequential);
+        self.exec_instr = instruction::Instruction::from_bytes(cur_pc, raw_exec_instr);
 
-        let raw_decode_instr = self.memory.get_word((cur_pc.saturating_add(4)) as usize);
+        let (raw_decode_instr, decode_cycles) =
+            self.fetch_word(cur_pc.saturating_add(4), AccessType::Sequential);
         self.decode_instr =
             instruction::Instruction::from_bytes(cur_pc.saturating_add(4), raw_decode_instr);
 
-        let raw_exec_instr = self.memory.get_word(cur_pc as usize);
-        self.exec_instr = instruction::Instruction::from_bytes(cur_pc, raw_exec_instr);
+        let (fetch_instr, fetch_cycles) =
+            self.fetch_word(cur_pc.saturating_add(8), AccessType::Sequential);
+        self.fetch_instr = fetch_instr;
+        self.fetch_addr = cur_pc.saturating_add(8);
+
+        exec_cycles + decode_cycles + fetch_cycles
     }
 
     pub fn tick_clock(&mut self, num_ticks: usize) -> Result<(), &'static str> {
@@ -105,31 +118,50 @@ impl Arm7TDMI {
 
         if self.is_idle {
             // Load initial pipeline contents
-            self.reload_pipeline();
+            self.clock_cycle += self.reload_pipeline();
             self.is_idle = false;
             return Ok(()); // Show loaded pipeline before executing first instruction
         }
 
+        if self.bus.irq_line() && self.get_cpsr() & CPSR_IRQ_DISABLE == 0 {
+            self.clock_cycle += self.enter_irq();
+            return Ok(());
+        }
+
         // Execute Exec instr
-        let control_flow_change = self
-            .exec_instr
-            .execute(&mut self.regfile, &mut self.memory)?;
+        let control_flow_change = self.exec_instr.execute(&mut self.regfile, &mut self.bus)?;
 
         if control_flow_change {
             // Flush and reload pipeline
-            self.reload_pipeline();
+            self.clock_cycle += self.reload_pipeline();
         } else {
             self.exec_instr = self.decode_instr;
             self.decode_instr = Instruction::from_bytes(self.fetch_addr, self.fetch_instr);
             let cur_pc = self.regfile.get_register(15);
+            let (fetch_instr, fetch_cycles) = self.fetch_word(cur_pc, AccessType::Sequential);
             self.fetch_addr = cur_pc;
-            self.fetch_instr = self.memory.get_word(cur_pc as usize);
+            self.fetch_instr = fetch_instr;
+            self.clock_cycle += fetch_cycles;
         }
 
-        self.clock_cycle += 1usize;
         Ok(())
     }
 
+    /// Takes the IRQ exception in place of the instruction about to execute, returning the
+    ///   cycles spent refilling the pipeline at the IRQ vector.
+    fn enter_irq(&mut self) -> usize {
+        let cur_cpsr = self.get_cpsr();
+        let _ = self.set_mode(OpMode::Irq);
+        // The handler returns with SUBS PC, LR, #4 to the instruction that was preempted
+        self.regfile
+            .set_register(14, self.exec_instr.address().wrapping_add(4));
+        self.regfile.set_register(17, cur_cpsr);
+        let _ = self.disable_irq();
+        let _ = self.enter_arm_mode();
+        self.set_pc(0x18);
+        self.reload_pipeline()
+    }
+
     pub fn print_state(&self) -> String {
         let mut ret_str: String = String::new();
         ret_str.push_str(format!("Current State: {:?}\n", &self.opmode).as_str());
@@ -153,8 +185,6 @@ impl Arm7TDMI {
         ret_str.push_str(self.regfile.print_cpsr_state().as_str());
         ret_str.push_str("\n\n");
 
-        ret_str.push_str(self.memory.print_memory(64).as_str());
-
         ret_str
     }
 
@@ -192,11 +222,11 @@ impl Arm7TDMI {
     }
 
     pub fn disable_fiq(&mut self) -> Result<(), &'static str> {
-        self.regfile.set_cpsr_bits(7, 1, 0b1)
+        self.regfile.set_cpsr_bits(6, 1, 0b1)
     }
 
     pub fn disable_irq(&mut self) -> Result<(), &'static str> {
-        self.regfile.set_cpsr_bits(6, 1, 0b1)
+        self.regfile.set_cpsr_bits(7, 1, 0b1)
     }
 
     pub fn enter_arm_mode(&mut self) -> Result<(), &'static str> {
@@ -207,3 +237,147 @@ impl Arm7TDMI {
         self.regfile.set_pc(new_pc);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAM_SIZE: usize = 1024;
+    /// `b` with an offset of 0, so every word decodes and any of them can be executed.
+    const BRANCH: u32 = 0xea00_0000;
+    const NONSEQUENTIAL_CYCLES: usize = 3;
+    const SEQUENTIAL_CYCLES: usize = 1;
+
+    /// A bus of plain RAM, mirrored across the address space, that logs each access.
+    struct FlatRam {
+        memory: Vec<u8>,
+        accesses: Vec<(u32, AccessType)>,
+        irq: bool,
+    }
+
+    impl Default for FlatRam {
+        fn default() -> Self {
+            Self {
+                memory: BRANCH.to_le_bytes().repeat(RAM_SIZE / 4),
+                accesses: Vec::new(),
+                irq: false,
+            }
+        }
+    }
+
+    impl FlatRam {
+        fn access(&mut self, address: u32, access: AccessType) -> (usize, usize) {
+            self.accesses.push((address, access));
+            let cycles = match access {
+                AccessType::NonSequential => NONSEQUENTIAL_CYCLES,
+                AccessType::Sequential => SEQUENTIAL_CYCLES,
+            };
+            (address as usize % RAM_SIZE, cycles)
+        }
+    }
+
+    impl Bus for FlatRam {
+        fn read_byte(&mut self, address: u32, access: AccessType) -> (u8, usize) {
+            let (offset, cycles) = self.access(address, access);
+            (self.memory[offset], cycles)
+        }
+
+        fn read_halfword(&mut self, address: u32, access: AccessType) -> (u16, usize) {
+            let (offset, cycles) = self.access(address & !1, access);
+            let bytes = [self.memory[offset], self.memory[offset + 1]];
+            (u16::from_le_bytes(bytes), cycles)
+        }
+
+        fn read_word(&mut self, address: u32, access: AccessType) -> (u32, usize) {
+            let (offset, cycles) = self.access(address & !3, access);
+            let bytes = self.memory[offset..offset + 4].try_into().unwrap();
+            (u32::from_le_bytes(bytes), cycles)
+        }
+
+        fn write_byte(&mut self, address: u32, value: u8, access: AccessType) -> usize {
+            let (offset, cycles) = self.access(address, access);
+            self.memory[offset] = value;
+            cycles
+        }
+
+        fn write_halfword(&mut self, address: u32, value: u16, access: AccessType) -> usize {
+            let (offset, cycles) = self.access(address & !1, access);
+            self.memory[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+            cycles
+        }
+
+        fn write_word(&mut self, address: u32, value: u32, access: AccessType) -> usize {
+            let (offset, cycles) = self.access(address & !3, access);
+            self.memory[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            cycles
+        }
+
+        fn irq_line(&self) -> bool {
+            self.irq
+        }
+    }
+
+    fn access_types(cpu: &Arm7TDMI<FlatRam>) -> Vec<AccessType> {
+        cpu.bus.accesses.iter().map(|(_, access)| *access).collect()
+    }
+
+    #[test]
+    fn pipeline_fill_fetches_nonsequentially_then_sequentially() {
+        let mut cpu = Arm7TDMI::<FlatRam>::default();
+        cpu.tick_clock(1).unwrap();
+
+        assert_eq!(
+            cpu.bus.accesses,
+            vec![
+                (0, AccessType::NonSequential),
+                (4, AccessType::Sequential),
+                (8, AccessType::Sequential),
+            ]
+        );
+        assert_eq!(
+            cpu.clock_cycle,
+            NONSEQUENTIAL_CYCLES + 2 * SEQUENTIAL_CYCLES
+        );
+    }
+
+    #[test]
+    fn branches_refill_the_pipeline() {
+        let mut cpu = Arm7TDMI::<FlatRam>::default();
+        for _ in 0..3 {
+            cpu.tick_clock(1).unwrap();
+        }
+
+        let refill = [
+            AccessType::NonSequential,
+            AccessType::Sequential,
+            AccessType::Sequential,
+        ];
+        assert_eq!(access_types(&cpu), refill.repeat(3));
+        assert_eq!(
+            cpu.clock_cycle,
+            3 * (NONSEQUENTIAL_CYCLES + 2 * SEQUENTIAL_CYCLES)
+        );
+    }
+
+    #[test]
+    fn pending_irqs_wait_while_the_i_bit_is_set() {
+        let mut cpu = Arm7TDMI::<FlatRam>::default();
+        cpu.bus.irq = true;
+        // Reset leaves both IRQs and FIQs masked
+        assert_eq!(cpu.get_cpsr() & 0xc0, 0xc0);
+        for _ in 0..3 {
+            cpu.tick_clock(1).unwrap();
+        }
+        assert!(cpu.bus.accesses.iter().all(|(address, _)| *address != 0x18));
+
+        // Masking FIQs alone lets the IRQ through
+        cpu.regfile.set_cpsr_bits(7, 1, 0).unwrap();
+        cpu.tick_clock(1).unwrap();
+        assert!(matches!(cpu.opmode, OpMode::Irq));
+        assert_eq!(
+            cpu.bus.accesses.last(),
+            Some(&(0x18 + 8, AccessType::Sequential))
+        );
+        assert_ne!(cpu.get_cpsr() & CPSR_IRQ_DISABLE, 0);
+    }
+}
@@ -69,11 +69,13 @@ impl RegFile {
             13 => match self.mode {
                 OpMode::User => self.r13,
                 OpMode::Supervisor => self.r13_svc,
+                OpMode::Irq => self.r13_irq,
                 _ => unimplemented!(),
             },
             14 => match self.mode {
                 OpMode::User => self.r14,
                 OpMode::Supervisor => self.r14_svc,
+                OpMode::Irq => self.r14_irq,
                 _ => unimplemented!(),
             },
             15 => self.r15_pc,
@@ -100,6 +102,7 @@ impl RegFile {
             13 => match self.mode {
                 OpMode::User => self.r13 = value,
                 OpMode::Supervisor => self.r13_svc = value,
+                OpMode::Irq => self.r13_irq = value,
                 _ => {
                     unimplemented!()
                 }
@@ -107,6 +110,7 @@ impl RegFile {
             14 => match self.mode {
                 OpMode::User => self.r14 = value,
                 OpMode::Supervisor => self.r14_svc = value,
+                OpMode::Irq => self.r14_irq = value,
                 _ => {
                     unimplemented!()
                 }
@@ -116,6 +120,7 @@ impl RegFile {
             17 => match self.mode {
                 OpMode::User => unimplemented!(), // SPSR not valid for user mode
                 OpMode::Supervisor => self.spsr_svc = value,
+                OpMode::Irq => self.spsr_irq = value,
                 _ => unimplemented!(),
             },
             _ => unimplemented!(),
@@ -145,6 +150,10 @@ impl RegFile {
                 self.mode = OpMode::Supervisor;
                 self.set_cpsr_bits(0, 5, 0b10011)
             }
+            OpMode::Irq => {
+                self.mode = OpMode::Irq;
+                self.set_cpsr_bits(0, 5, 0b10010)
+            }
             _ => {
                 unimplemented!()
             }
@@ -0,0 +1,150 @@
+#[cfg(all(feature = "cpal", not(target_arch = "wasm32")))]
+mod native;
+mod resampler;
+mod wav;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+#[cfg(all(feature = "cpal", not(target_arch = "wasm32")))]
+pub use native::CpalSink;
+use resampler::Resampler;
+pub use wav::encode_wav;
+#[cfg(not(target_arch = "wasm32"))]
+pub use wav::WavSink;
+#[cfg(target_arch = "wasm32")]
+pub use web::WebAudioSink;
+
+/// A common output rate, used until a sink reports its own.
+pub const DEFAULT_OUTPUT_RATE: u32 = 48000;
+/// How far ahead of playback the sink is kept filled.
+const TARGET_LATENCY_SECONDS: f64 = 0.05;
+/// Names a WAV file to write the output to instead of playing it, for headless runs.
+#[cfg(not(target_arch = "wasm32"))]
+const WAV_OUTPUT_VAR: &str = "GBAEMU_AUDIO_WAV";
+/// The most the playback speed is adjusted by to steer the buffer level, small enough that
+///   the change in pitch can't be heard.
+const MAX_SPEED_ADJUSTMENT: f64 = 0.005;
+
+/// Somewhere resampled audio is sent to be played or stored.
+pub trait AudioSink {
+    /// The rate the sink expects `push` to be called with.
+    fn sample_rate(&self) -> u32;
+
+    /// Queues left and right samples in -1.0 to 1.0.
+    fn push(&mut self, frames: &[[f32; 2]]);
+
+    /// Samples queued but not yet played, for sinks that play back in real time.
+    fn buffered_frames(&self) -> Option<usize>;
+}
+
+/// Discards everything, for running without sound.
+pub struct NullSink {
+    sample_rate: u32,
+}
+
+impl NullSink {
+    pub fn new(sample_rate: u32) -> Self {
+        Self { sample_rate }
+    }
+}
+
+impl AudioSink for NullSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, _frames: &[[f32; 2]]) {}
+
+    fn buffered_frames(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Takes the emulator's samples to a sink, resampling them to the sink's rate. Real-time
+///   sinks drift away from the emulator's clock, so the resampling rate is nudged to keep
+///   their buffer near a fixed latency, which avoids both underruns and growing delay.
+pub struct AudioOutput {
+    sink: Box<dyn AudioSink>,
+    resampler: Resampler,
+    scratch: Vec<[f32; 2]>,
+}
+
+impl Default for AudioOutput {
+    /// A WAV file if `GBAEMU_AUDIO_WAV` names one, else the sound device if built with the
+    ///   `cpal` feature, else nothing.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn default() -> Self {
+        if let Some(path) = std::env::var_os(WAV_OUTPUT_VAR) {
+            match WavSink::create(std::path::Path::new(&path), DEFAULT_OUTPUT_RATE) {
+                Ok(sink) => return Self::new(Box::new(sink)),
+                Err(err) => println!("Could not create {}: {}", path.to_string_lossy(), err),
+            }
+        }
+        match device_sink() {
+            Ok(sink) => Self::new(sink),
+            Err(err) => {
+                println!("Audio output unavailable: {}", err);
+                Self::new(Box::new(NullSink::new(DEFAULT_OUTPUT_RATE)))
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn default() -> Self {
+        match WebAudioSink::new() {
+            Ok(sink) => Self::new(Box::new(sink)),
+            Err(err) => {
+                println!("Audio output unavailable: {}", err);
+                Self::new(Box::new(NullSink::new(DEFAULT_OUTPUT_RATE)))
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "cpal", not(target_arch = "wasm32")))]
+fn device_sink() -> Result<Box<dyn AudioSink>, &'static str> {
+    Ok(Box::new(CpalSink::new()?))
+}
+
+#[cfg(all(not(feature = "cpal"), not(target_arch = "wasm32")))]
+fn device_sink() -> Result<Box<dyn AudioSink>, &'static str> {
+    Err("built without the cpal feature")
+}
+
+impl AudioOutput {
+    pub fn new(sink: Box<dyn AudioSink>) -> Self {
+        let resampler = Resampler::new(DEFAULT_OUTPUT_RATE, sink.sample_rate());
+        Self {
+            sink,
+            resampler,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Resamples `samples`, produced at `input_rate`, and passes them to the sink.
+    pub fn process(&mut self, input_rate: u32, samples: &[[i16; 2]]) {
+        if samples.is_empty() {
+            return;
+        }
+        self.resampler.set_input_rate(input_rate);
+        let input: Vec<[f32; 2]> = samples
+            .iter()
+            .map(|frame| frame.map(|sample| sample as f32 / 32768.0))
+            .collect();
+
+        self.scratch.clear();
+        self.resampler
+            .process(&input, self.playback_speed(), &mut self.scratch);
+        self.sink.push(&self.scratch);
+    }
+
+    /// Plays faster when the sink's buffer is above the target, and slower when it is below.
+    fn playback_speed(&self) -> f64 {
+        let Some(buffered) = self.sink.buffered_frames() else {
+            return 1.0;
+        };
+        let target = self.sink.sample_rate() as f64 * TARGET_LATENCY_SECONDS;
+        let error = (buffered as f64 - target) / target;
+        1.0 + (error * MAX_SPEED_ADJUSTMENT).clamp(-MAX_SPEED_ADJUSTMENT, MAX_SPEED_ADJUSTMENT)
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+
+use super::AudioSink;
+
+/// The most the ring buffer holds, in seconds. Beyond it the oldest frames are dropped, so
+///   a stalled device can't make the delay grow without bound.
+const MAX_BUFFERED_SECONDS: usize = 1;
+
+type RingBuffer = Arc<Mutex<VecDeque<[f32; 2]>>>;
+
+/// Plays audio on the default output device through cpal. Pushed frames wait in a ring
+///   buffer that the device's callback drains.
+pub struct CpalSink {
+    // Playback stops when the stream is dropped
+    _stream: cpal::Stream,
+    buffer: RingBuffer,
+    sample_rate: u32,
+}
+
+impl CpalSink {
+    pub fn new() -> Result<Self, &'static str> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("No audio output device")?;
+        let supported = device
+            .default_output_config()
+            .map_err(|_| "Could not query the audio output format")?;
+        let sample_rate = supported.sample_rate().0;
+        let format = supported.sample_format();
+        let config: cpal::StreamConfig = supported.into();
+
+        let buffer = RingBuffer::default();
+        let stream = match format {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, buffer.clone()),
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, buffer.clone()),
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, buffer.clone()),
+            _ => return Err("Unsupported audio sample format"),
+        }
+        .map_err(|_| "Could not open the audio output stream")?;
+        stream
+            .play()
+            .map_err(|_| "Could not start the audio output stream")?;
+
+        Ok(Self {
+            _stream: stream,
+            buffer,
+            sample_rate,
+        })
+    }
+}
+
+/// A stream writing the ring buffer's frames to the device, with silence when it runs dry.
+///   A mono device gets the left and right average, as do the extra channels of a device
+///   with more than two.
+fn build_stream<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    buffer: RingBuffer,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut buffer = buffer.lock().unwrap();
+            for frame in data.chunks_mut(channels) {
+                let [left, right] = buffer.pop_front().unwrap_or([0.0; 2]);
+                for (channel, sample) in frame.iter_mut().enumerate() {
+                    let value = match channel {
+                        0 if channels > 1 => left,
+                        1 => right,
+                        _ => (left + right) / 2.0,
+                    };
+                    *sample = T::from_sample(value);
+                }
+            }
+        },
+        |err| println!("Audio output error: {}", err),
+        None,
+    )
+}
+
+impl AudioSink for CpalSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, frames: &[[f32; 2]]) {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.extend(frames);
+        let max_len = self.sample_rate as usize * MAX_BUFFERED_SECONDS;
+        if buffer.len() > max_len {
+            let excess = buffer.len() - max_len;
+            buffer.drain(..excess);
+        }
+    }
+
+    fn buffered_frames(&self) -> Option<usize> {
+        Some(self.buffer.lock().unwrap().len())
+    }
+}
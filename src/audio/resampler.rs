@@ -0,0 +1,110 @@
+use std::f64::consts::PI;
+
+/// Input samples on each side of the output point that contribute to it.
+const HALF_TAPS: usize = 8;
+const TAPS: usize = HALF_TAPS * 2;
+/// Fractional positions the filter is tabulated at.
+const PHASES: usize = 256;
+/// How much of the band below the lower Nyquist frequency is kept, leaving the rest for
+///   the filter to roll off in.
+const PASSBAND: f64 = 0.9;
+
+/// Converts stereo samples between rates with a windowed sinc filter, which removes the
+///   frequencies the output rate can't represent instead of letting them alias.
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    // Filter weights, `TAPS` per phase
+    table: Vec<f32>,
+    // Input samples not yet fully used, starting `HALF_TAPS - 1` samples before `position`
+    history: Vec<[f32; 2]>,
+    // Position of the next output sample, in input samples from the start of `history`
+    position: f64,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        let mut resampler = Self {
+            input_rate: 0,
+            output_rate,
+            table: Vec::new(),
+            history: Vec::new(),
+            position: 0.0,
+        };
+        resampler.set_input_rate(input_rate);
+        resampler
+    }
+
+    /// Rebuilds the filter when the emulated sample rate changes.
+    pub fn set_input_rate(&mut self, input_rate: u32) {
+        if input_rate == self.input_rate {
+            return;
+        }
+        self.input_rate = input_rate;
+        self.history = vec![[0.0; 2]; TAPS];
+        self.position = (HALF_TAPS - 1) as f64;
+
+        // Below the input's Nyquist frequency, and the output's when downsampling
+        let cutoff = PASSBAND * (self.output_rate as f64 / input_rate as f64).min(1.0);
+        self.table = (0..PHASES)
+            .flat_map(|phase| {
+                let fraction = phase as f64 / PHASES as f64;
+                let weights: Vec<f64> = (0..TAPS)
+                    .map(|tap| {
+                        let x = tap as f64 - (HALF_TAPS - 1) as f64 - fraction;
+                        cutoff * sinc(cutoff * x) * blackman(x / HALF_TAPS as f64)
+                    })
+                    .collect();
+                // Normalized so a constant signal keeps its level
+                let total: f64 = weights.iter().sum();
+                weights
+                    .into_iter()
+                    .map(move |weight| (weight / total) as f32)
+            })
+            .collect();
+    }
+
+    /// Resamples `input`, appending to `output`. `speed` above 1 consumes the input faster,
+    ///   producing fewer samples; rate control nudges it to keep the sink's buffer level.
+    pub fn process(&mut self, input: &[[f32; 2]], speed: f64, output: &mut Vec<[f32; 2]>) {
+        self.history.extend_from_slice(input);
+        let step = self.input_rate as f64 / self.output_rate as f64 * speed;
+
+        while self.position as usize + HALF_TAPS < self.history.len() {
+            let index = self.position as usize;
+            let phase = ((self.position - index as f64) * PHASES as f64) as usize;
+            let weights = &self.table[phase * TAPS..][..TAPS];
+            let window = &self.history[index + 1 - HALF_TAPS..][..TAPS];
+
+            let mut sample = [0.0; 2];
+            for (weight, input) in weights.iter().zip(window) {
+                sample[0] += weight * input[0];
+                sample[1] += weight * input[1];
+            }
+            output.push(sample);
+            self.position += step;
+        }
+
+        // Drop the samples no future output reaches back to
+        let consumed = (self.position as usize + 1).saturating_sub(HALF_TAPS);
+        self.history.drain(..consumed.min(self.history.len()));
+        self.position -= consumed as f64;
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// The Blackman window, over -1 to 1.
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        return 0.0;
+    }
+    let t = PI * (x + 1.0);
+    0.42 - 0.5 * t.cos() + 0.08 * (2.0 * t).cos()
+}
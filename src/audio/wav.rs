@@ -0,0 +1,145 @@
+use std::io::{Cursor, Seek, SeekFrom, Write};
+
+const HEADER_SIZE: u32 = 44;
+const CHANNELS: u16 = 2;
+const BYTES_PER_SAMPLE: u16 = 2;
+
+/// Writes 16-bit stereo PCM as a WAV file. The header's sizes are filled in by `finish`.
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+    frames: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut writer: W, sample_rate: u32) -> std::io::Result<Self> {
+        writer.write_all(&header(sample_rate, 0))?;
+        Ok(Self {
+            writer,
+            sample_rate,
+            frames: 0,
+        })
+    }
+
+    pub fn write(&mut self, frames: &[[i16; 2]]) -> std::io::Result<()> {
+        let bytes: Vec<u8> = frames
+            .iter()
+            .flat_map(|frame| frame.iter().flat_map(|sample| sample.to_le_bytes()))
+            .collect();
+        self.writer.write_all(&bytes)?;
+        self.frames += frames.len() as u32;
+        Ok(())
+    }
+
+    /// Rewrites the header with the final sizes.
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer
+            .write_all(&header(self.sample_rate, self.frames))?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+}
+
+/// A complete WAV file holding `frames`.
+pub fn encode_wav(sample_rate: u32, frames: &[[i16; 2]]) -> Vec<u8> {
+    let encode = || -> std::io::Result<Vec<u8>> {
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), sample_rate)?;
+        writer.write(frames)?;
+        writer.finish()?;
+        Ok(writer.writer.into_inner())
+    };
+    encode().expect("Writing to memory can't fail")
+}
+
+fn header(sample_rate: u32, frames: u32) -> Vec<u8> {
+    let block_align = CHANNELS * BYTES_PER_SAMPLE;
+    let data_size = frames * block_align as u32;
+    let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(HEADER_SIZE - 8 + data_size).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    // Uncompressed PCM
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&CHANNELS.to_le_bytes());
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&(BYTES_PER_SAMPLE * 8).to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_size.to_le_bytes());
+    header
+}
+
+/// Writes the output to a WAV file instead of playing it, for running without a sound
+///   device. The file is finished when the sink is dropped.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct WavSink {
+    writer: WavWriter<std::io::BufWriter<std::fs::File>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl WavSink {
+    pub fn create(path: &std::path::Path, sample_rate: u32) -> std::io::Result<Self> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        Ok(Self {
+            writer: WavWriter::new(file, sample_rate)?,
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl super::AudioSink for WavSink {
+    fn sample_rate(&self) -> u32 {
+        self.writer.sample_rate
+    }
+
+    fn push(&mut self, frames: &[[f32; 2]]) {
+        let frames: Vec<[i16; 2]> = frames
+            .iter()
+            .map(|frame| frame.map(|sample| (sample * 32768.0).clamp(-32768.0, 32767.0) as i16))
+            .collect();
+        if let Err(err) = self.writer.write(&frames) {
+            println!("Failed to write audio: {}", err);
+        }
+    }
+
+    fn buffered_frames(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for WavSink {
+    fn drop(&mut self) {
+        if let Err(err) = self.writer.finish() {
+            println!("Failed to finish audio file: {}", err);
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::audio::AudioOutput;
+
+    #[test]
+    fn wav_sink_writes_the_resampled_output() {
+        let path = std::env::temp_dir().join("gbaemu-wav-sink-test.wav");
+        let sink = WavSink::create(&path, 48000).unwrap();
+        let mut output = AudioOutput::new(Box::new(sink));
+        // A second of samples at 32768 Hz
+        output.process(32768, &vec![[0x1000, -0x1000]; 32768]);
+        drop(output);
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let frames = (bytes.len() - HEADER_SIZE as usize) / 4;
+        assert_eq!(
+            &bytes[..HEADER_SIZE as usize],
+            &header(48000, frames as u32)[..]
+        );
+        assert!((47900..=48100).contains(&frames), "{} frames", frames);
+    }
+}
@@ -0,0 +1,82 @@
+use web_sys::{AudioContext, AudioContextState};
+
+use super::AudioSink;
+
+/// Plays audio through the browser's WebAudio API, scheduling each pushed block to start
+///   where the previous one ends.
+pub struct WebAudioSink {
+    context: AudioContext,
+    sample_rate: u32,
+    // Context time the next block starts at
+    next_time: f64,
+}
+
+impl WebAudioSink {
+    pub fn new() -> Result<Self, &'static str> {
+        let context = AudioContext::new().map_err(|_| "Could not create an AudioContext")?;
+        let sample_rate = context.sample_rate() as u32;
+        Ok(Self {
+            context,
+            sample_rate,
+            next_time: 0.0,
+        })
+    }
+
+    fn schedule(&mut self, frames: &[[f32; 2]]) -> Result<(), &'static str> {
+        let buffer = self
+            .context
+            .create_buffer(2, frames.len() as u32, self.sample_rate as f32)
+            .map_err(|_| "Could not create an audio buffer")?;
+        for channel in 0..2 {
+            let samples: Vec<f32> = frames.iter().map(|frame| frame[channel]).collect();
+            buffer
+                .copy_to_channel(&samples, channel as i32)
+                .map_err(|_| "Could not fill an audio buffer")?;
+        }
+
+        let source = self
+            .context
+            .create_buffer_source()
+            .map_err(|_| "Could not create an audio source")?;
+        source.set_buffer(Some(&buffer));
+        source
+            .connect_with_audio_node(&self.context.destination())
+            .map_err(|_| "Could not connect an audio source")?;
+
+        // After an underrun, restart a little ahead instead of in the past
+        let now = self.context.current_time();
+        if self.next_time < now {
+            self.next_time = now + super::TARGET_LATENCY_SECONDS;
+        }
+        source
+            .start_with_when(self.next_time)
+            .map_err(|_| "Could not start an audio source")?;
+        self.next_time += frames.len() as f64 / self.sample_rate as f64;
+        Ok(())
+    }
+}
+
+impl AudioSink for WebAudioSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, frames: &[[f32; 2]]) {
+        if frames.is_empty() {
+            return;
+        }
+        // Browsers keep the context suspended until the page has been interacted with
+        if self.context.state() == AudioContextState::Suspended {
+            let _ = self.context.resume();
+            return;
+        }
+        if let Err(err) = self.schedule(frames) {
+            println!("{}", err);
+        }
+    }
+
+    fn buffered_frames(&self) -> Option<usize> {
+        let ahead = (self.next_time - self.context.current_time()).max(0.0);
+        Some((ahead * self.sample_rate as f64) as usize)
+    }
+}
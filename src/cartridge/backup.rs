@@ -0,0 +1,157 @@
+use std::fmt;
+
+use super::eeprom::Eeprom;
+use super::flash::{Flash, FlashChip};
+use super::sram::Sram;
+
+/// Kind of battery-backed storage carried by a cartridge.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum BackupType {
+    #[default]
+    None,
+    Sram,
+    Flash64K,
+    Flash128K,
+    Eeprom,
+}
+
+impl BackupType {
+    pub const ALL: [BackupType; 5] = [
+        BackupType::None,
+        BackupType::Sram,
+        BackupType::Flash64K,
+        BackupType::Flash128K,
+        BackupType::Eeprom,
+    ];
+
+    /// Scans a ROM image for the ID string Nintendo's save library embeds.
+    pub fn detect(rom: &[u8]) -> Self {
+        // The library strings are always word aligned, and no string is a prefix of another,
+        //   so the first hit decides the backup type.
+        const LIBRARY_IDS: [(&[u8], BackupType); 6] = [
+            (b"SRAM_V", BackupType::Sram),
+            (b"SRAM_F_V", BackupType::Sram),
+            (b"FLASH_V", BackupType::Flash64K),
+            (b"FLASH512_V", BackupType::Flash64K),
+            (b"FLASH1M_V", BackupType::Flash128K),
+            (b"EEPROM_V", BackupType::Eeprom),
+        ];
+
+        for offset in (0..rom.len()).step_by(4) {
+            for (id, backup_type) in LIBRARY_IDS {
+                if rom[offset..].starts_with(id) {
+                    return backup_type;
+                }
+            }
+        }
+        BackupType::None
+    }
+
+    /// Size of the backing store in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::None | Self::Eeprom => 0,
+            Self::Sram => super::sram::SRAM_SIZE,
+            Self::Flash64K => 64 * 1024,
+            Self::Flash128K => 128 * 1024,
+        }
+    }
+}
+
+/// The cartridge's save device. SRAM and Flash answer in the 0x0E000000 region,
+///   while the EEPROM is reached through the upper ROM bus instead.
+pub enum Backup {
+    None,
+    Sram(Sram),
+    Flash(Flash),
+    Eeprom(Eeprom),
+}
+
+impl Backup {
+    pub fn new(backup_type: BackupType, flash_chip: Option<FlashChip>) -> Self {
+        match backup_type {
+            BackupType::Sram => Backup::Sram(Sram::default()),
+            BackupType::Flash64K | BackupType::Flash128K => {
+                Backup::Flash(Flash::new(flash_chip_for(backup_type, flash_chip)))
+            }
+            BackupType::Eeprom => Backup::Eeprom(Eeprom::default()),
+            BackupType::None => Backup::None,
+        }
+    }
+
+    /// Changes which part a Flash backup answers ID mode as, keeping its contents.
+    pub fn set_flash_chip(&mut self, backup_type: BackupType, flash_chip: Option<FlashChip>) {
+        if let Self::Flash(flash) = self {
+            flash.set_chip(flash_chip_for(backup_type, flash_chip));
+        }
+    }
+
+    pub fn read(&self, address: u32) -> u8 {
+        match self {
+            Self::Sram(sram) => sram.read(address),
+            Self::Flash(flash) => flash.read(address),
+            // Nothing answers in the SRAM region, so the bus floats high
+            Self::Eeprom(_) | Self::None => 0xff,
+        }
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) {
+        match self {
+            Self::Sram(sram) => sram.write(address, value),
+            Self::Flash(flash) => flash.write(address, value),
+            Self::Eeprom(_) | Self::None => {}
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        match self {
+            Self::Sram(sram) => sram.data(),
+            Self::Flash(flash) => flash.data(),
+            Self::Eeprom(eeprom) => eeprom.data(),
+            Self::None => &[],
+        }
+    }
+
+    /// Overwrites the device contents, padding short images with erased (0xff) bytes.
+    pub fn load_data(&mut self, bytes: &[u8]) {
+        let data = match self {
+            Self::Sram(sram) => sram.data_mut(),
+            Self::Flash(flash) => flash.data_mut(),
+            Self::Eeprom(eeprom) => eeprom.data_mut(),
+            Self::None => return,
+        };
+        let num_bytes = bytes.len().min(data.len());
+        data[..num_bytes].copy_from_slice(&bytes[..num_bytes]);
+        data[num_bytes..].fill(0xff);
+    }
+
+    /// Reports whether the contents changed since the last call.
+    pub fn take_dirty(&mut self) -> bool {
+        match self {
+            Self::Sram(sram) => sram.take_dirty(),
+            Self::Flash(flash) => flash.take_dirty(),
+            Self::Eeprom(eeprom) => eeprom.take_dirty(),
+            Self::None => false,
+        }
+    }
+}
+
+/// The configured Flash part if it has the right size, else the default one for the size.
+fn flash_chip_for(backup_type: BackupType, flash_chip: Option<FlashChip>) -> FlashChip {
+    let size = backup_type.size();
+    flash_chip
+        .filter(|chip| chip.size() == size)
+        .unwrap_or(FlashChip::default_for_size(size))
+}
+
+impl fmt::Display for BackupType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::Sram => write!(f, "SRAM 32K"),
+            Self::Flash64K => write!(f, "Flash 64K"),
+            Self::Flash128K => write!(f, "Flash 128K"),
+            Self::Eeprom => write!(f, "EEPROM"),
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::fmt;
+
+/// Number of status polls answered with "busy" after a block has been written.
+const WRITE_BUSY_POLLS: u32 = 16;
+/// Reads shift out 4 junk bits ahead of the 64 data bits.
+const READ_STREAM_BITS: usize = 68;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum EepromSize {
+    Small512,
+    Large8K,
+}
+
+impl EepromSize {
+    pub fn address_bits(&self) -> usize {
+        match self {
+            Self::Small512 => 6,
+            Self::Large8K => 14,
+        }
+    }
+
+    pub fn bytes(&self) -> usize {
+        match self {
+            Self::Small512 => 512,
+            Self::Large8K => 8 * 1024,
+        }
+    }
+
+    /// Infers the chip size from the length of a save dump.
+    pub fn from_save_length(num_bytes: usize) -> Option<Self> {
+        match num_bytes {
+            512 => Some(Self::Small512),
+            8192 => Some(Self::Large8K),
+            _ => None,
+        }
+    }
+
+    /// Infers the chip size from the length of a DMA3 transfer aimed at the EEPROM.
+    ///   Read requests are 2 command bits, the address and a stop bit; writes add 64 data bits.
+    pub fn from_dma_length(num_units: usize) -> Option<Self> {
+        match num_units {
+            9 | 73 => Some(Self::Small512),
+            17 | 81 => Some(Self::Large8K),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for EepromSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Small512 => write!(f, "512B"),
+            Self::Large8K => write!(f, "8K"),
+        }
+    }
+}
+
+/// Serial EEPROM, accessed one bit per halfword transfer (in practice always through DMA3).
+pub struct Eeprom {
+    data: Vec<u8>,
+    size: Option<EepromSize>,
+    incoming: u128,
+    incoming_len: usize,
+    outgoing: u64,
+    outgoing_len: usize,
+    busy_polls: u32,
+    dirty: bool,
+}
+
+impl Default for Eeprom {
+    fn default() -> Self {
+        Self {
+            data: vec![0xffu8; EepromSize::Large8K.bytes()],
+            size: None,
+            incoming: 0,
+            incoming_len: 0,
+            outgoing: 0,
+            outgoing_len: 0,
+            busy_polls: 0,
+            dirty: false,
+        }
+    }
+}
+
+impl Eeprom {
+    /// The detected chip size, or the 8K part if no transfer has revealed it yet.
+    pub fn size(&self) -> EepromSize {
+        self.size.unwrap_or(EepromSize::Large8K)
+    }
+
+    /// Contents of the chip; only the first `size().bytes()` are addressable.
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.size().bytes()]
+    }
+
+    /// Always the full 8K buffer, so a save can be loaded before the size is known.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    pub fn observe_dma_length(&mut self, num_units: usize) {
+        if let Some(size) = EepromSize::from_dma_length(num_units) {
+            self.set_size(size);
+        }
+    }
+
+    /// Takes the size of an imported save dump, so it is exported at the same size.
+    pub fn observe_save_length(&mut self, num_bytes: usize) {
+        if let Some(size) = EepromSize::from_save_length(num_bytes) {
+            self.set_size(size);
+        }
+    }
+
+    fn set_size(&mut self, size: EepromSize) {
+        if self.size != Some(size) {
+            println!("Detected EEPROM size: {}", size);
+        }
+        self.size = Some(size);
+    }
+
+    pub fn read(&mut self) -> u16 {
+        if self.outgoing_len > 0 {
+            self.outgoing_len -= 1;
+            if self.outgoing_len >= 64 {
+                return 0;
+            }
+            return ((self.outgoing >> self.outgoing_len) & 1) as u16;
+        }
+
+        // Bit 0 doubles as the "ready" flag once a write has been issued
+        if self.busy_polls > 0 {
+            self.busy_polls -= 1;
+            return 0;
+        }
+        1
+    }
+
+    pub fn write(&mut self, value: u16) {
+        self.incoming = (self.incoming << 1) | (value & 1) as u128;
+        self.incoming_len += 1;
+        if self.incoming_len < 2 {
+            return;
+        }
+
+        let address_bits = self.size().address_bits();
+        let command = (self.incoming >> (self.incoming_len - 2)) & 0b11;
+        let request_len = match command {
+            0b11 => 2 + address_bits + 1,
+            0b10 => 2 + address_bits + 64 + 1,
+            _ => {
+                // Not a valid request; drop the stream and wait for the next one
+                self.incoming = 0;
+                self.incoming_len = 0;
+                return;
+            }
+        };
+        if self.incoming_len < request_len {
+            return;
+        }
+
+        let address_mask = (1u128 << address_bits) - 1;
+        if command == 0b11 {
+            let block = ((self.incoming >> 1) & address_mask) as usize;
+            self.outgoing = u64::from_be_bytes(self.block(block).try_into().unwrap());
+            self.outgoing_len = READ_STREAM_BITS;
+        } else {
+            let block = ((self.incoming >> 65) & address_mask) as usize;
+            let bits = (self.incoming >> 1) as u64;
+            self.block_mut(block).copy_from_slice(&bits.to_be_bytes());
+            self.busy_polls = WRITE_BUSY_POLLS;
+            self.dirty = true;
+        }
+
+        self.incoming = 0;
+        self.incoming_len = 0;
+    }
+
+    fn block(&self, block: usize) -> &[u8] {
+        let start = (block * 8) % self.size().bytes();
+        &self.data[start..start + 8]
+    }
+
+    fn block_mut(&mut self, block: usize) -> &mut [u8] {
+        let start = (block * 8) % self.size().bytes();
+        &mut self.data[start..start + 8]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: u64 = 0x0123_4567_89ab_cdef;
+
+    /// Appends the low `len` bits of `value` to `bits`, most significant first.
+    fn push_bits(bits: &mut Vec<u16>, value: u64, len: usize) {
+        bits.extend((0..len).rev().map(|bit| ((value >> bit) & 1) as u16));
+    }
+
+    fn send(eeprom: &mut Eeprom, bits: &[u16]) {
+        for bit in bits {
+            eeprom.write(*bit);
+        }
+    }
+
+    fn write_request(size: EepromSize, block: u64, data: u64) -> Vec<u16> {
+        let mut bits = vec![1, 0];
+        push_bits(&mut bits, block, size.address_bits());
+        push_bits(&mut bits, data, 64);
+        bits.push(0);
+        bits
+    }
+
+    fn read_request(size: EepromSize, block: u64) -> Vec<u16> {
+        let mut bits = vec![1, 1];
+        push_bits(&mut bits, block, size.address_bits());
+        bits.push(0);
+        bits
+    }
+
+    /// Reads the whole stream a read request produces.
+    fn read_stream(eeprom: &mut Eeprom) -> Vec<u16> {
+        (0..READ_STREAM_BITS).map(|_| eeprom.read()).collect()
+    }
+
+    fn expected_stream(data: u64) -> Vec<u16> {
+        let mut bits = vec![0; 4];
+        push_bits(&mut bits, data, 64);
+        bits
+    }
+
+    #[test]
+    fn request_lengths_match_dma_lengths() {
+        for size in [EepromSize::Small512, EepromSize::Large8K] {
+            let read_len = read_request(size, 0).len();
+            let write_len = write_request(size, 0, 0).len();
+            assert_eq!(EepromSize::from_dma_length(read_len), Some(size));
+            assert_eq!(EepromSize::from_dma_length(write_len), Some(size));
+        }
+    }
+
+    #[test]
+    fn detects_size_from_dma_lengths() {
+        assert_eq!(EepromSize::from_dma_length(9), Some(EepromSize::Small512));
+        assert_eq!(EepromSize::from_dma_length(73), Some(EepromSize::Small512));
+        assert_eq!(EepromSize::from_dma_length(17), Some(EepromSize::Large8K));
+        assert_eq!(EepromSize::from_dma_length(81), Some(EepromSize::Large8K));
+        assert_eq!(EepromSize::from_dma_length(68), None);
+
+        let mut eeprom = Eeprom::default();
+        assert_eq!(eeprom.size(), EepromSize::Large8K);
+        eeprom.observe_dma_length(9);
+        assert_eq!(eeprom.size(), EepromSize::Small512);
+        assert_eq!(eeprom.data().len(), 512);
+        eeprom.observe_dma_length(81);
+        assert_eq!(eeprom.size(), EepromSize::Large8K);
+        assert_eq!(eeprom.data().len(), 8 * 1024);
+    }
+
+    #[test]
+    fn detects_size_from_save_lengths() {
+        let mut eeprom = Eeprom::default();
+        eeprom.observe_save_length(512);
+        assert_eq!(eeprom.size(), EepromSize::Small512);
+        eeprom.observe_save_length(1000);
+        assert_eq!(eeprom.size(), EepromSize::Small512);
+        eeprom.observe_save_length(8192);
+        assert_eq!(eeprom.size(), EepromSize::Large8K);
+    }
+
+    #[test]
+    fn writes_and_reads_back_a_block_with_14_bit_addresses() {
+        let size = EepromSize::Large8K;
+        let mut eeprom = Eeprom::default();
+        eeprom.observe_dma_length(81);
+
+        send(&mut eeprom, &write_request(size, 0x123, DATA));
+        assert!(eeprom.take_dirty());
+        assert_eq!(&eeprom.data()[0x123 * 8..0x124 * 8], &DATA.to_be_bytes());
+
+        send(&mut eeprom, &read_request(size, 0x123));
+        assert_eq!(read_stream(&mut eeprom), expected_stream(DATA));
+    }
+
+    #[test]
+    fn writes_and_reads_back_a_block_with_6_bit_addresses() {
+        let size = EepromSize::Small512;
+        let mut eeprom = Eeprom::default();
+        eeprom.observe_dma_length(9);
+
+        send(&mut eeprom, &write_request(size, 0x3f, DATA));
+        assert_eq!(&eeprom.data()[504..512], &DATA.to_be_bytes());
+
+        send(&mut eeprom, &read_request(size, 0x3f));
+        assert_eq!(read_stream(&mut eeprom), expected_stream(DATA));
+    }
+
+    #[test]
+    fn reports_busy_after_a_write_then_ready() {
+        let mut eeprom = Eeprom::default();
+        assert_eq!(eeprom.read(), 1);
+
+        send(&mut eeprom, &write_request(EepromSize::Large8K, 0, DATA));
+        for _ in 0..WRITE_BUSY_POLLS {
+            assert_eq!(eeprom.read(), 0);
+        }
+        assert_eq!(eeprom.read(), 1);
+    }
+
+    #[test]
+    fn reads_ready_once_the_stream_is_exhausted() {
+        let mut eeprom = Eeprom::default();
+        send(&mut eeprom, &read_request(EepromSize::Large8K, 0));
+        assert_eq!(read_stream(&mut eeprom), expected_stream(u64::MAX));
+        assert_eq!(eeprom.read(), 1);
+    }
+
+    #[test]
+    fn drops_invalid_requests() {
+        let mut eeprom = Eeprom::default();
+        // A stream starting 0b01 is discarded, leaving the next bits to start a new request
+        send(&mut eeprom, &[0, 1]);
+        send(&mut eeprom, &write_request(EepromSize::Large8K, 1, DATA));
+        assert_eq!(&eeprom.data()[8..16], &DATA.to_be_bytes());
+    }
+}
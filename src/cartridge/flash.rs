@@ -0,0 +1,371 @@
+use std::fmt;
+
+const BANK_SIZE: usize = 64 * 1024;
+const SECTOR_SIZE: usize = 4 * 1024;
+const ATMEL_PAGE_SIZE: usize = 128;
+
+/// Flash parts found in retail cartridges, each answering ID mode with its own codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum FlashChip {
+    Macronix64K,
+    Panasonic64K,
+    Atmel64K,
+    Macronix128K,
+    Sanyo128K,
+}
+
+impl FlashChip {
+    pub const ALL: [FlashChip; 5] = [
+        FlashChip::Macronix64K,
+        FlashChip::Panasonic64K,
+        FlashChip::Atmel64K,
+        FlashChip::Macronix128K,
+        FlashChip::Sanyo128K,
+    ];
+
+    /// The chip used when nothing else is configured for a given capacity.
+    pub fn default_for_size(size: usize) -> Self {
+        if size > BANK_SIZE {
+            FlashChip::Macronix128K
+        } else {
+            FlashChip::Panasonic64K
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Macronix64K | Self::Panasonic64K | Self::Atmel64K => BANK_SIZE,
+            Self::Macronix128K | Self::Sanyo128K => 2 * BANK_SIZE,
+        }
+    }
+
+    pub fn manufacturer_id(&self) -> u8 {
+        match self {
+            Self::Macronix64K | Self::Macronix128K => 0xc2,
+            Self::Panasonic64K => 0x32,
+            Self::Atmel64K => 0x1f,
+            Self::Sanyo128K => 0x62,
+        }
+    }
+
+    pub fn device_id(&self) -> u8 {
+        match self {
+            Self::Macronix64K => 0x1c,
+            Self::Panasonic64K => 0x1b,
+            Self::Atmel64K => 0x3d,
+            Self::Macronix128K => 0x09,
+            Self::Sanyo128K => 0x13,
+        }
+    }
+}
+
+impl fmt::Display for FlashChip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Macronix64K => write!(f, "Macronix 64K"),
+            Self::Panasonic64K => write!(f, "Panasonic 64K"),
+            Self::Atmel64K => write!(f, "Atmel 64K"),
+            Self::Macronix128K => write!(f, "Macronix 128K"),
+            Self::Sanyo128K => write!(f, "Sanyo 128K"),
+        }
+    }
+}
+
+/// Progress through the `0xAA -> 0x5555, 0x55 -> 0x2AAA` unlock sequence preceding each command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unlock {
+    Idle,
+    GotAa,
+    Got55,
+}
+
+/// What the next plain write to the chip is interpreted as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pending {
+    None,
+    ProgramByte,
+    // Atmel parts program a whole page at a time; holds the number of bytes still expected
+    ProgramPage(usize),
+    BankSelect,
+}
+
+pub struct Flash {
+    chip: FlashChip,
+    data: Vec<u8>,
+    bank: usize,
+    id_mode: bool,
+    erase_armed: bool,
+    unlock: Unlock,
+    pending: Pending,
+    dirty: bool,
+}
+
+impl Flash {
+    pub fn new(chip: FlashChip) -> Self {
+        Self {
+            chip,
+            data: vec![0xffu8; chip.size()],
+            bank: 0,
+            id_mode: false,
+            erase_armed: false,
+            unlock: Unlock::Idle,
+            pending: Pending::None,
+            dirty: false,
+        }
+    }
+
+    /// Swaps in another part of the same size, which only changes the ID mode reply and
+    ///   how programming works.
+    pub fn set_chip(&mut self, chip: FlashChip) {
+        if chip.size() == self.chip.size() {
+            self.chip = chip;
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    pub fn read(&self, address: u32) -> u8 {
+        let offset = (address as usize) & (BANK_SIZE - 1);
+        if self.id_mode {
+            match offset {
+                0 => return self.chip.manufacturer_id(),
+                1 => return self.chip.device_id(),
+                _ => {}
+            }
+        }
+        self.data[self.bank * BANK_SIZE + offset]
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) {
+        let offset = (address as usize) & (BANK_SIZE - 1);
+
+        match self.pending {
+            Pending::None => {}
+            Pending::ProgramByte => {
+                // Programming can only clear bits; setting them again needs an erase
+                self.data[self.bank * BANK_SIZE + offset] &= value;
+                self.pending = Pending::None;
+                self.dirty = true;
+                return;
+            }
+            Pending::ProgramPage(remaining) => {
+                if remaining == ATMEL_PAGE_SIZE {
+                    // The page is erased as part of the program operation
+                    let page_start = self.bank * BANK_SIZE + (offset & !(ATMEL_PAGE_SIZE - 1));
+                    self.data[page_start..page_start + ATMEL_PAGE_SIZE].fill(0xff);
+                }
+                self.data[self.bank * BANK_SIZE + offset] = value;
+                self.dirty = true;
+                self.pending = if remaining > 1 {
+                    Pending::ProgramPage(remaining - 1)
+                } else {
+                    Pending::None
+                };
+                return;
+            }
+            Pending::BankSelect => {
+                if offset == 0 {
+                    self.bank = (value as usize) & (self.chip.size() / BANK_SIZE - 1);
+                }
+                self.pending = Pending::None;
+                return;
+            }
+        }
+
+        self.unlock = match (self.unlock, offset, value) {
+            (Unlock::Idle, 0x5555, 0xaa) => Unlock::GotAa,
+            (Unlock::GotAa, 0x2aaa, 0x55) => Unlock::Got55,
+            (Unlock::Got55, 0x5555, command) => {
+                self.run_command(command);
+                Unlock::Idle
+            }
+            (Unlock::Got55, sector, 0x30) if self.erase_armed => {
+                let sector_start = self.bank * BANK_SIZE + (sector & !(SECTOR_SIZE - 1));
+                self.data[sector_start..sector_start + SECTOR_SIZE].fill(0xff);
+                self.erase_armed = false;
+                self.dirty = true;
+                Unlock::Idle
+            }
+            _ => Unlock::Idle,
+        };
+    }
+
+    fn run_command(&mut self, command: u8) {
+        let erase_armed = self.erase_armed;
+        self.erase_armed = false;
+
+        match command {
+            0x90 => self.id_mode = true,
+            0xf0 => self.id_mode = false,
+            0x80 => self.erase_armed = true,
+            0x10 if erase_armed => {
+                self.data.fill(0xff);
+                self.dirty = true;
+            }
+            0xa0 => {
+                self.pending = if self.chip == FlashChip::Atmel64K {
+                    Pending::ProgramPage(ATMEL_PAGE_SIZE)
+                } else {
+                    Pending::ProgramByte
+                }
+            }
+            0xb0 if self.chip.size() > BANK_SIZE => self.pending = Pending::BankSelect,
+            _ => println!("Ignoring unknown flash command: {:#04x}", command),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: u32 = 0x0e00_0000;
+
+    fn command(flash: &mut Flash, command: u8) {
+        flash.write(BASE + 0x5555, 0xaa);
+        flash.write(BASE + 0x2aaa, 0x55);
+        flash.write(BASE + 0x5555, command);
+    }
+
+    fn program(flash: &mut Flash, address: u32, value: u8) {
+        command(flash, 0xa0);
+        flash.write(address, value);
+    }
+
+    #[test]
+    fn id_mode_answers_with_the_chip_codes() {
+        for chip in FlashChip::ALL {
+            let mut flash = Flash::new(chip);
+            command(&mut flash, 0x90);
+            assert_eq!(flash.read(BASE), chip.manufacturer_id());
+            assert_eq!(flash.read(BASE + 1), chip.device_id());
+
+            command(&mut flash, 0xf0);
+            assert_eq!(flash.read(BASE), 0xff);
+            assert_eq!(flash.read(BASE + 1), 0xff);
+        }
+    }
+
+    #[test]
+    fn set_chip_keeps_the_contents() {
+        let mut flash = Flash::new(FlashChip::Panasonic64K);
+        program(&mut flash, BASE + 0x10, 0x5a);
+        flash.set_chip(FlashChip::Macronix64K);
+        command(&mut flash, 0x90);
+        assert_eq!(flash.read(BASE), FlashChip::Macronix64K.manufacturer_id());
+        command(&mut flash, 0xf0);
+        assert_eq!(flash.read(BASE + 0x10), 0x5a);
+
+        // A part of another size doesn't fit the storage
+        flash.set_chip(FlashChip::Sanyo128K);
+        command(&mut flash, 0x90);
+        assert_eq!(flash.read(BASE), FlashChip::Macronix64K.manufacturer_id());
+    }
+
+    #[test]
+    fn programming_only_clears_bits() {
+        let mut flash = Flash::new(FlashChip::Panasonic64K);
+        program(&mut flash, BASE + 0x10, 0x5a);
+        assert_eq!(flash.read(BASE + 0x10), 0x5a);
+        assert!(flash.take_dirty());
+
+        program(&mut flash, BASE + 0x10, 0x0f);
+        assert_eq!(flash.read(BASE + 0x10), 0x0a);
+        // Without a command, writes are ignored
+        flash.write(BASE + 0x11, 0x00);
+        assert_eq!(flash.read(BASE + 0x11), 0xff);
+    }
+
+    #[test]
+    fn a_broken_unlock_sequence_is_ignored() {
+        let mut flash = Flash::new(FlashChip::Panasonic64K);
+        flash.write(BASE + 0x5555, 0xaa);
+        flash.write(BASE + 0x2aaa, 0x54);
+        flash.write(BASE + 0x5555, 0xa0);
+        flash.write(BASE + 0x10, 0x00);
+        assert_eq!(flash.read(BASE + 0x10), 0xff);
+        assert!(!flash.take_dirty());
+    }
+
+    #[test]
+    fn sector_erase_clears_one_sector() {
+        let mut flash = Flash::new(FlashChip::Panasonic64K);
+        program(&mut flash, BASE + 0x1000, 0x00);
+        program(&mut flash, BASE + 0x1fff, 0x00);
+        program(&mut flash, BASE + 0x2000, 0x00);
+
+        command(&mut flash, 0x80);
+        flash.write(BASE + 0x5555, 0xaa);
+        flash.write(BASE + 0x2aaa, 0x55);
+        flash.write(BASE + 0x1000, 0x30);
+        assert_eq!(flash.read(BASE + 0x1000), 0xff);
+        assert_eq!(flash.read(BASE + 0x1fff), 0xff);
+        assert_eq!(flash.read(BASE + 0x2000), 0x00);
+    }
+
+    #[test]
+    fn chip_erase_needs_to_be_armed() {
+        let mut flash = Flash::new(FlashChip::Panasonic64K);
+        program(&mut flash, BASE, 0x00);
+
+        command(&mut flash, 0x10);
+        assert_eq!(flash.read(BASE), 0x00);
+
+        command(&mut flash, 0x80);
+        command(&mut flash, 0x10);
+        assert_eq!(flash.read(BASE), 0xff);
+    }
+
+    #[test]
+    fn bank_select_reaches_the_second_bank() {
+        let mut flash = Flash::new(FlashChip::Macronix128K);
+        command(&mut flash, 0xb0);
+        flash.write(BASE, 1);
+        program(&mut flash, BASE + 0x10, 0x42);
+        assert_eq!(flash.data()[BANK_SIZE + 0x10], 0x42);
+        assert_eq!(flash.data()[0x10], 0xff);
+
+        command(&mut flash, 0xb0);
+        flash.write(BASE, 0);
+        assert_eq!(flash.read(BASE + 0x10), 0xff);
+    }
+
+    #[test]
+    fn bank_select_is_ignored_on_64k_parts() {
+        let mut flash = Flash::new(FlashChip::Panasonic64K);
+        command(&mut flash, 0xb0);
+        // Taken as a plain write instead of a bank number
+        flash.write(BASE, 1);
+        program(&mut flash, BASE + 0x10, 0x42);
+        assert_eq!(flash.data()[0x10], 0x42);
+    }
+
+    #[test]
+    fn atmel_programs_whole_pages() {
+        let mut flash = Flash::new(FlashChip::Atmel64K);
+        program(&mut flash, BASE + 0x100, 0x00);
+        for offset in 1..ATMEL_PAGE_SIZE as u32 {
+            flash.write(BASE + 0x100 + offset, offset as u8);
+        }
+        assert_eq!(flash.read(BASE + 0x100), 0x00);
+        assert_eq!(flash.read(BASE + 0x17f), 0x7f);
+
+        // Reprogramming the page erases it first, so bits can be set again
+        program(&mut flash, BASE + 0x100, 0xff);
+        for offset in 1..ATMEL_PAGE_SIZE as u32 {
+            flash.write(BASE + 0x100 + offset, 0xff);
+        }
+        assert_eq!(flash.read(BASE + 0x100), 0xff);
+        assert_eq!(flash.read(BASE + 0x17f), 0xff);
+    }
+}
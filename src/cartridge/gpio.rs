@@ -0,0 +1,88 @@
+use super::rtc::{Rtc, RtcClock};
+use super::sensors::{GyroSensor, Rumble, SensorInput, SolarSensor};
+
+const GPIO_DATA: u32 = 0x080000c4;
+const GPIO_DIRECTION: u32 = 0x080000c6;
+const GPIO_CONTROL: u32 = 0x080000c8;
+
+/// The 4-bit general purpose I/O port some cartridges map over the ROM at 0x080000C4,
+///   along with the devices wired to it.
+#[derive(Default)]
+pub struct Gpio {
+    data: u8,
+    direction: u8,
+    readable: bool,
+    pub rtc: Option<Rtc>,
+    pub solar: Option<SolarSensor>,
+    pub gyro: Option<GyroSensor>,
+    pub rumble: Option<Rumble>,
+}
+
+impl Gpio {
+    pub fn is_gpio_address(address: u32) -> bool {
+        (GPIO_DATA..GPIO_CONTROL + 2).contains(&address)
+    }
+
+    /// Without any device attached the port isn't there, and accesses fall through to the ROM.
+    pub fn is_present(&self) -> bool {
+        self.rtc.is_some() || self.solar.is_some() || self.gyro.is_some() || self.rumble.is_some()
+    }
+
+    pub fn attach_rtc(&mut self, rtc_clock: Option<RtcClock>) {
+        self.rtc = rtc_clock.map(Rtc::new);
+    }
+
+    pub fn attach_sensors(&mut self, solar: bool, gyro: bool, rumble: bool) {
+        self.solar = solar.then(SolarSensor::default);
+        self.gyro = gyro.then(GyroSensor::default);
+        self.rumble = rumble.then(Rumble::default);
+    }
+
+    /// Returns `None` while the port is write-only, in which case the ROM shows through.
+    pub fn read_byte(&self, address: u32) -> Option<u8> {
+        if !self.readable {
+            return None;
+        }
+
+        let value = match address & !1 {
+            GPIO_DATA => self.pins(),
+            GPIO_DIRECTION => self.direction,
+            GPIO_CONTROL => self.readable as u8,
+            _ => unreachable!(),
+        };
+        // Only the low byte of each register is wired
+        Some(if address & 1 == 0 { value } else { 0 })
+    }
+
+    pub fn write_halfword(&mut self, address: u32, value: u16, input: &SensorInput) {
+        match address & !1 {
+            GPIO_DATA => {
+                self.data = (value & 0xf) as u8;
+                let driven_pins = self.data & self.direction;
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_pins(driven_pins);
+                }
+                if let Some(solar) = &mut self.solar {
+                    solar.write_pins(driven_pins, input);
+                }
+                if let Some(gyro) = &mut self.gyro {
+                    gyro.write_pins(driven_pins, input);
+                }
+                if let Some(rumble) = &mut self.rumble {
+                    rumble.write_pins(driven_pins);
+                }
+            }
+            GPIO_DIRECTION => self.direction = (value & 0xf) as u8,
+            GPIO_CONTROL => self.readable = (value & 1) != 0,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Output pins report the last value written, input pins whatever the devices drive.
+    fn pins(&self) -> u8 {
+        let device_pins = self.rtc.as_ref().map_or(0, |rtc| rtc.output_pins())
+            | self.solar.as_ref().map_or(0, |solar| solar.output_pins())
+            | self.gyro.as_ref().map_or(0, |gyro| gyro.output_pins());
+        (self.data & self.direction) | (device_pins & !self.direction)
+    }
+}
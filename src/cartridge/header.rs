@@ -0,0 +1,33 @@
+/// The fields of the cartridge header at 0x080000A0 that identify a game.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub game_code: String,
+    pub maker_code: String,
+    pub version: u8,
+}
+
+impl CartridgeHeader {
+    pub fn parse(rom: &[u8]) -> Option<Self> {
+        let header = rom.get(0xa0..0xc0)?;
+        let ascii = |bytes: &[u8]| -> String {
+            bytes
+                .iter()
+                .take_while(|byte| **byte != 0)
+                .map(|byte| *byte as char)
+                .collect()
+        };
+
+        Some(Self {
+            title: ascii(&header[0x00..0x0c]),
+            game_code: ascii(&header[0x0c..0x10]),
+            maker_code: ascii(&header[0x10..0x12]),
+            version: header[0x1c],
+        })
+    }
+
+    /// The game code without its trailing region letter, shared by all releases of a game.
+    pub fn game_id(&self) -> &str {
+        self.game_code.get(..3).unwrap_or(&self.game_code)
+    }
+}
@@ -0,0 +1,339 @@
+mod backup;
+mod eeprom;
+mod flash;
+mod gpio;
+mod header;
+mod rtc;
+mod sensors;
+mod sram;
+
+use backup::Backup;
+pub use backup::BackupType;
+pub use flash::FlashChip;
+use gpio::Gpio;
+pub use header::CartridgeHeader;
+pub use rtc::RtcClock;
+use rtc::{Rtc, RTC_STATE_SIZE};
+pub use sensors::SensorInput;
+use sensors::TiltSensor;
+
+/// Largest ROM the Game Pak bus can address.
+pub const MAX_ROM_SIZE: usize = 32 * 1024 * 1024;
+
+/// Games shipping with the S-3511 real-time clock, by game code without the region letter.
+const RTC_GAME_IDS: [&str; 8] = ["AXV", "AXP", "BPE", "BKA", "BR4", "U3I", "U32", "U33"];
+/// Boktai 1-3.
+const SOLAR_GAME_IDS: [&str; 3] = ["U3I", "U32", "U33"];
+/// Yoshi Topsy-Turvy and Koro Koro Puzzle Happy Panechu!
+const TILT_GAME_IDS: [&str; 2] = ["KYG", "KHP"];
+/// WarioWare: Twisted!
+const GYRO_GAME_IDS: [&str; 1] = ["RZW"];
+/// WarioWare: Twisted! and Drill Dozer.
+const RUMBLE_GAME_IDS: [&str; 2] = ["RZW", "V49"];
+
+pub struct Cartridge {
+    rom: Vec<u8>,
+    header: Option<CartridgeHeader>,
+    // Detected from the ROM, or from an imported save needing a larger Flash part
+    detected_backup: BackupType,
+    backup_override: Option<BackupType>,
+    flash_chip: Option<FlashChip>,
+    backup: Backup,
+    detected_rtc: bool,
+    rtc_override: Option<bool>,
+    rtc_clock: RtcClock,
+    gpio: Gpio,
+    tilt: Option<TiltSensor>,
+    sensor_input: SensorInput,
+}
+
+impl Default for Cartridge {
+    fn default() -> Self {
+        Self {
+            rom: vec![],
+            header: None,
+            detected_backup: BackupType::None,
+            backup_override: None,
+            flash_chip: None,
+            backup: Backup::None,
+            detected_rtc: false,
+            rtc_override: None,
+            rtc_clock: RtcClock::default(),
+            gpio: Gpio::default(),
+            tilt: None,
+            sensor_input: SensorInput::default(),
+        }
+    }
+}
+
+impl Cartridge {
+    pub fn load_rom(&mut self, rom_bytes: &[u8]) -> Result<(), &'static str> {
+        if rom_bytes.len() > MAX_ROM_SIZE {
+            return Err("Rom image too large: Expected at most 32MiB");
+        }
+
+        self.rom = rom_bytes.to_owned();
+        self.header = CartridgeHeader::parse(&self.rom);
+        self.detected_backup = BackupType::detect(&self.rom);
+        println!("Detected backup type: {}", self.detected_backup);
+        self.attach_backup();
+        self.detected_rtc = self.detect_rtc();
+        self.attach_gpio();
+        Ok(())
+    }
+
+    pub fn header(&self) -> Option<&CartridgeHeader> {
+        self.header.as_ref()
+    }
+
+    /// Looks for a game known to carry an RTC, or for the RTC library Pokemon links in.
+    fn detect_rtc(&self) -> bool {
+        let known_game = self
+            .header
+            .as_ref()
+            .is_some_and(|header| RTC_GAME_IDS.contains(&header.game_id()));
+        known_game || self.rom.windows(8).step_by(4).any(|id| id == b"SIIRTC_V")
+    }
+
+    pub fn detected_rtc(&self) -> bool {
+        self.detected_rtc
+    }
+
+    pub fn rtc_override(&self) -> Option<bool> {
+        self.rtc_override
+    }
+
+    pub fn set_rtc_override(&mut self, rtc_override: Option<bool>) {
+        self.rtc_override = rtc_override;
+        self.attach_gpio();
+    }
+
+    pub fn rtc_clock(&self) -> RtcClock {
+        self.rtc_clock
+    }
+
+    pub fn set_rtc_clock(&mut self, rtc_clock: RtcClock) {
+        self.rtc_clock = rtc_clock;
+        if let Some(rtc) = &mut self.gpio.rtc {
+            rtc.set_clock(rtc_clock);
+        }
+    }
+
+    fn attach_gpio(&mut self) {
+        let has_rtc = self.rtc_override.unwrap_or(self.detected_rtc);
+        self.gpio.attach_rtc(has_rtc.then_some(self.rtc_clock));
+
+        let game_id = self.header.as_ref().map_or("", |header| header.game_id());
+        self.gpio.attach_sensors(
+            SOLAR_GAME_IDS.contains(&game_id),
+            GYRO_GAME_IDS.contains(&game_id),
+            RUMBLE_GAME_IDS.contains(&game_id),
+        );
+        self.tilt = TILT_GAME_IDS.contains(&game_id).then(TiltSensor::default);
+    }
+
+    pub fn has_solar_sensor(&self) -> bool {
+        self.gpio.solar.is_some()
+    }
+
+    pub fn has_tilt_sensor(&self) -> bool {
+        self.tilt.is_some()
+    }
+
+    pub fn has_gyro_sensor(&self) -> bool {
+        self.gpio.gyro.is_some()
+    }
+
+    pub fn has_rumble(&self) -> bool {
+        self.gpio.rumble.is_some()
+    }
+
+    pub fn rumble_active(&self) -> bool {
+        self.gpio
+            .rumble
+            .as_ref()
+            .is_some_and(|rumble| rumble.is_active())
+    }
+
+    pub fn sensor_input(&self) -> SensorInput {
+        self.sensor_input
+    }
+
+    pub fn set_sensor_input(&mut self, sensor_input: SensorInput) {
+        self.sensor_input = sensor_input;
+    }
+
+    pub fn detected_backup_type(&self) -> BackupType {
+        self.detected_backup
+    }
+
+    pub fn backup_override(&self) -> Option<BackupType> {
+        self.backup_override
+    }
+
+    /// Rewires the backup as another type, carrying its contents across so the loaded save
+    ///   isn't lost.
+    pub fn set_backup_override(&mut self, backup_override: Option<BackupType>) {
+        let previous_type = self.backup_type();
+        self.backup_override = backup_override;
+        if self.backup_type() == previous_type {
+            return;
+        }
+        let backup = Backup::new(self.backup_type(), self.flash_chip);
+        let previous = std::mem::replace(&mut self.backup, backup);
+        self.backup.load_data(previous.data());
+    }
+
+    pub fn flash_chip(&self) -> Option<FlashChip> {
+        self.flash_chip
+    }
+
+    /// Selects which Flash part answers ID mode; `None` picks one matching the backup size.
+    pub fn set_flash_chip(&mut self, flash_chip: Option<FlashChip>) {
+        self.flash_chip = flash_chip;
+        self.backup.set_flash_chip(self.backup_type(), flash_chip);
+    }
+
+    /// Wires up empty storage of the current backup type, for a newly loaded ROM.
+    fn attach_backup(&mut self) {
+        self.backup = Backup::new(self.backup_type(), self.flash_chip);
+    }
+
+    /// The backup type actually wired to the bus: the manual override if set, else the detected one.
+    pub fn backup_type(&self) -> BackupType {
+        self.backup_override.unwrap_or(self.detected_backup)
+    }
+
+    /// The backup contents alone, as a raw dump other emulators and flash carts accept.
+    pub fn backup_data(&self) -> &[u8] {
+        self.backup.data()
+    }
+
+    /// The backup contents, followed by the RTC state when the cartridge has one.
+    pub fn save_data(&self) -> Vec<u8> {
+        let mut savebytes = self.backup.data().to_owned();
+        if let Some(rtc) = &self.gpio.rtc {
+            savebytes.extend_from_slice(&rtc.save_state());
+        }
+        savebytes
+    }
+
+    /// Whether the backup or the RTC state changed since the last call.
+    pub fn take_save_dirty(&mut self) -> bool {
+        let rtc_dirty = self.gpio.rtc.as_mut().is_some_and(|rtc| rtc.take_dirty());
+        self.backup.take_dirty() | rtc_dirty
+    }
+
+    /// Runs the cartridge's clock for `cycles` CPU cycles.
+    pub fn step(&mut self, cycles: usize) {
+        if let Some(rtc) = &mut self.gpio.rtc {
+            rtc.step(cycles);
+        }
+    }
+
+    /// Loads a raw save dump, adapting to dumps sized for a different part than detected.
+    pub fn import_save(&mut self, bytes: &[u8]) -> Result<(), &'static str> {
+        if let Backup::None = self.backup {
+            return Err("Cartridge has no backup memory to import a save into");
+        }
+
+        let mut bytes = bytes;
+        if bytes.len() > RTC_STATE_SIZE {
+            let (backup_bytes, rtc_state) = bytes.split_at(bytes.len() - RTC_STATE_SIZE);
+            if Rtc::is_state(rtc_state) {
+                if let Some(rtc) = &mut self.gpio.rtc {
+                    rtc.load_state(rtc_state);
+                }
+                bytes = backup_bytes;
+            }
+        }
+
+        let expected_size = self.backup_type().size();
+        if self.backup_type() == BackupType::Flash64K && bytes.len() > expected_size {
+            // Some emulators write every Flash save as 128K; only switch parts if bank 1 is in
+            //   use, and never against a part picked by hand
+            if bytes[expected_size..].iter().any(|byte| *byte != 0xff) {
+                if self.backup_override.is_some() {
+                    println!("Save uses a second Flash bank, but 64K Flash was selected");
+                } else {
+                    println!("Save uses a second Flash bank, switching to 128K Flash");
+                    self.detected_backup = BackupType::Flash128K;
+                    self.attach_backup();
+                }
+            }
+        }
+
+        if let Backup::Eeprom(eeprom) = &mut self.backup {
+            eeprom.observe_save_length(bytes.len());
+        }
+        self.backup.load_data(bytes);
+        Ok(())
+    }
+
+    pub fn read_rom_byte(&self, address: u32) -> u8 {
+        if self.gpio.is_present() && Gpio::is_gpio_address(address) {
+            if let Some(value) = self.gpio.read_byte(address) {
+                return value;
+            }
+        }
+
+        let offset = (address as usize) & (MAX_ROM_SIZE - 1);
+        match self.rom.get(offset) {
+            Some(byte) => *byte,
+            // Reads past the end of the ROM return the low bits of the halfword address
+            None => ((offset >> 1) >> ((offset & 1) * 8)) as u8,
+        }
+    }
+
+    /// The ROM itself ignores writes, but the GPIO port is mapped over it.
+    pub fn write_rom_halfword(&mut self, address: u32, value: u16) {
+        if self.gpio.is_present() && Gpio::is_gpio_address(address) {
+            self.gpio.write_halfword(address, value, &self.sensor_input)
+        }
+    }
+
+    /// EEPROM sits on the upper ROM bus: the whole 0x0D region for ROMs of 16MiB or less,
+    ///   and only its last 256 bytes for larger ones.
+    pub fn is_eeprom_address(&self, address: u32) -> bool {
+        if self.backup_type() != BackupType::Eeprom || (address >> 24) != 0x0d {
+            return false;
+        }
+        self.rom.len() <= 16 * 1024 * 1024 || address >= 0x0dffff00
+    }
+
+    pub fn read_backup_byte(&self, address: u32) -> u8 {
+        match &self.tilt {
+            Some(tilt) if TiltSensor::is_tilt_address(address) => tilt.read(address),
+            _ => self.backup.read(address),
+        }
+    }
+
+    pub fn write_backup_byte(&mut self, address: u32, value: u8) {
+        match &mut self.tilt {
+            Some(tilt) if TiltSensor::is_tilt_address(address) => {
+                tilt.write(address, value, &self.sensor_input)
+            }
+            _ => self.backup.write(address, value),
+        }
+    }
+
+    pub fn read_eeprom(&mut self) -> u16 {
+        match &mut self.backup {
+            Backup::Eeprom(eeprom) => eeprom.read(),
+            _ => 1,
+        }
+    }
+
+    pub fn write_eeprom(&mut self, value: u16) {
+        if let Backup::Eeprom(eeprom) = &mut self.backup {
+            eeprom.write(value)
+        }
+    }
+
+    /// Lets a DMA3 transfer touching the EEPROM reveal the chip's address width.
+    pub fn observe_eeprom_dma(&mut self, num_units: usize) {
+        if let Backup::Eeprom(eeprom) = &mut self.backup {
+            eeprom.observe_dma_length(num_units)
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use std::fmt;
+
+/// GPIO pins used by the serial interface.
+pub const PIN_SCK: u8 = 0b001;
+pub const PIN_SIO: u8 = 0b010;
+pub const PIN_CS: u8 = 0b100;
+
+const CONTROL_HOUR_24: u8 = 0x40;
+
+const COMMAND_RESET: u8 = 0;
+const COMMAND_DATETIME: u8 = 2;
+const COMMAND_CONTROL: u8 = 4;
+const COMMAND_TIME: u8 = 6;
+
+/// Size of the RTC state appended to battery saves.
+pub const RTC_STATE_SIZE: usize = 16;
+const RTC_STATE_MAGIC: &[u8; 4] = b"RTC\0";
+
+/// CPU cycles per second, which the fixed clock counts time in.
+const CYCLES_PER_SECOND: u64 = 1 << 24;
+
+/// Where the RTC takes the current date and time from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum RtcClock {
+    #[default]
+    Host,
+    /// Starts at 2000-01-01 00:00:00 (plus whatever the game sets) and runs with emulated
+    ///   time, for reproducible runs.
+    Fixed,
+}
+
+impl fmt::Display for RtcClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Host => write!(f, "Host clock"),
+            Self::Fixed => write!(f, "Fixed clock"),
+        }
+    }
+}
+
+/// Progress of the chip select handshake that starts each transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transfer {
+    Idle,
+    Selecting,
+    Active,
+}
+
+/// Seiko S-3511 serial real-time clock, wired to the cartridge GPIO port.
+pub struct Rtc {
+    clock: RtcClock,
+    // Seconds between the clock source and the time the game last set
+    offset_secs: i64,
+    // Emulated time since power on, which the fixed clock runs on
+    elapsed_cycles: u64,
+    // Whether the game changed the time or settings since the last save
+    dirty: bool,
+    control: u8,
+    transfer: Transfer,
+    last_pins: u8,
+    bits: u8,
+    bits_read: u8,
+    command: Option<u8>,
+    bytes_remaining: usize,
+    time: [u8; 7],
+    sio_out: u8,
+}
+
+impl Rtc {
+    pub fn new(clock: RtcClock) -> Self {
+        Self {
+            clock,
+            offset_secs: 0,
+            elapsed_cycles: 0,
+            dirty: false,
+            control: CONTROL_HOUR_24,
+            transfer: Transfer::Idle,
+            last_pins: 0,
+            bits: 0,
+            bits_read: 0,
+            command: None,
+            bytes_remaining: 0,
+            time: [0u8; 7],
+            sio_out: 0,
+        }
+    }
+
+    /// Switches clock source without the time jumping.
+    pub fn set_clock(&mut self, clock: RtcClock) {
+        let now = self.now();
+        self.clock = clock;
+        self.offset_secs = (now - self.source_time()).num_seconds();
+    }
+
+    pub fn step(&mut self, cycles: usize) {
+        self.elapsed_cycles += cycles as u64;
+    }
+
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    /// The pins driven by the chip, as seen by the GBA.
+    pub fn output_pins(&self) -> u8 {
+        self.sio_out
+    }
+
+    pub fn write_pins(&mut self, pins: u8) {
+        let rising_edge = (pins & PIN_SCK) != 0 && (self.last_pins & PIN_SCK) == 0;
+        self.last_pins = pins;
+
+        match self.transfer {
+            Transfer::Idle => {
+                if pins & (PIN_SCK | PIN_CS) == PIN_SCK {
+                    self.transfer = Transfer::Selecting;
+                }
+            }
+            Transfer::Selecting => {
+                if pins & (PIN_SCK | PIN_CS) == (PIN_SCK | PIN_CS) {
+                    self.transfer = Transfer::Active;
+                } else if pins & (PIN_SCK | PIN_CS) != PIN_SCK {
+                    self.transfer = Transfer::Idle;
+                }
+            }
+            Transfer::Active => {
+                if pins & PIN_CS == 0 {
+                    // Dropping chip select aborts whatever was in flight
+                    self.end_command();
+                    self.transfer = if pins & PIN_SCK != 0 {
+                        Transfer::Selecting
+                    } else {
+                        Transfer::Idle
+                    };
+                    self.sio_out = 0;
+                } else if pins & PIN_SCK == 0 {
+                    // Data is set up while the clock is low and latched on its rising edge
+                    let bit = (pins & PIN_SIO) >> 1;
+                    self.bits = (self.bits & !(1 << self.bits_read)) | (bit << self.bits_read);
+                } else if rising_edge {
+                    self.clock_bit();
+                }
+            }
+        }
+    }
+
+    fn is_reading(&self) -> bool {
+        self.command.is_some_and(|command| command & 0x80 != 0)
+    }
+
+    fn clock_bit(&mut self) {
+        if !self.is_reading() {
+            self.bits_read += 1;
+            if self.bits_read == 8 {
+                self.process_byte();
+            }
+            return;
+        }
+
+        self.sio_out = ((self.output_byte() >> self.bits_read) & 1) << 1;
+        self.bits_read += 1;
+        if self.bits_read == 8 {
+            self.bits_read = 0;
+            self.bytes_remaining -= 1;
+            if self.bytes_remaining == 0 {
+                self.end_command();
+            }
+        }
+    }
+
+    fn output_byte(&self) -> u8 {
+        match self.command.map(|command| (command >> 4) & 0b111) {
+            Some(COMMAND_CONTROL) => self.control,
+            Some(COMMAND_DATETIME) | Some(COMMAND_TIME) => self.time[7 - self.bytes_remaining],
+            _ => 0,
+        }
+    }
+
+    fn process_byte(&mut self) {
+        match self.command {
+            None => {
+                // Bits arrive LSB first, leaving the fixed 0110 pattern in the low nibble
+                if self.bits & 0x0f != 0b0110 {
+                    println!("Ignoring invalid RTC command byte: {:#04x}", self.bits);
+                } else {
+                    let command = (self.bits >> 4) & 0b111;
+                    self.command = Some(self.bits);
+                    self.bytes_remaining = match command {
+                        COMMAND_DATETIME => 7,
+                        COMMAND_TIME => 3,
+                        COMMAND_CONTROL => 1,
+                        _ => 0,
+                    };
+                    match command {
+                        COMMAND_RESET => {
+                            self.control = 0;
+                            self.set_time(Self::epoch());
+                            self.dirty = true;
+                        }
+                        COMMAND_DATETIME | COMMAND_TIME => self.latch_time(),
+                        _ => {}
+                    }
+                }
+            }
+            Some(command) => {
+                match (command >> 4) & 0b111 {
+                    COMMAND_CONTROL => {
+                        self.control = self.bits;
+                        self.dirty = true;
+                    }
+                    COMMAND_DATETIME | COMMAND_TIME => {
+                        self.time[7 - self.bytes_remaining] = self.bits;
+                        if self.bytes_remaining == 1 {
+                            self.apply_time();
+                        }
+                    }
+                    _ => {}
+                }
+                self.bytes_remaining -= 1;
+            }
+        }
+
+        self.bits = 0;
+        self.bits_read = 0;
+        if self.bytes_remaining == 0 {
+            self.end_command();
+        }
+    }
+
+    fn end_command(&mut self) {
+        self.command = None;
+        self.bits = 0;
+        self.bits_read = 0;
+        self.bytes_remaining = 0;
+    }
+
+    fn epoch() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2000, 1, 1)
+            .unwrap()
+            .and_time(NaiveTime::MIN)
+    }
+
+    fn source_time(&self) -> NaiveDateTime {
+        match self.clock {
+            RtcClock::Host => Local::now().naive_local(),
+            RtcClock::Fixed => {
+                let elapsed_secs = self.elapsed_cycles / CYCLES_PER_SECOND;
+                Self::epoch() + Duration::seconds(elapsed_secs as i64)
+            }
+        }
+    }
+
+    fn now(&self) -> NaiveDateTime {
+        self.source_time() + Duration::seconds(self.offset_secs)
+    }
+
+    fn set_time(&mut self, time: NaiveDateTime) {
+        self.offset_secs = (time - self.source_time()).num_seconds();
+    }
+
+    /// Snapshots the current date and time into the BCD registers read by the game.
+    fn latch_time(&mut self) {
+        let now = self.now();
+        let hour = if self.control & CONTROL_HOUR_24 != 0 {
+            now.hour()
+        } else {
+            now.hour() % 12
+        };
+        // Bit 7 of the hour is the PM flag, set in both 12 and 24 hour modes
+        let pm_flag = if now.hour() >= 12 { 0x80 } else { 0 };
+
+        self.time = [
+            to_bcd((now.year() % 100) as u32),
+            to_bcd(now.month()),
+            to_bcd(now.day()),
+            to_bcd(now.weekday().num_days_from_sunday()),
+            to_bcd(hour) | pm_flag,
+            to_bcd(now.minute()),
+            to_bcd(now.second()),
+        ];
+    }
+
+    /// Adopts the date and time the game just wrote to the BCD registers.
+    fn apply_time(&mut self) {
+        let now = self.now();
+        let hour = from_bcd(self.time[4] & 0x3f);
+        let hour = if self.control & CONTROL_HOUR_24 == 0 && self.time[4] & 0x80 != 0 {
+            hour % 12 + 12
+        } else {
+            hour
+        };
+
+        // The time-only command leaves the date alone
+        let date = if self.command.map(|command| (command >> 4) & 0b111) == Some(COMMAND_TIME) {
+            Some(now.date())
+        } else {
+            NaiveDate::from_ymd_opt(
+                2000 + from_bcd(self.time[0]) as i32,
+                from_bcd(self.time[1]),
+                from_bcd(self.time[2]),
+            )
+        };
+        let time = NaiveTime::from_hms_opt(hour, from_bcd(self.time[5]), from_bcd(self.time[6]));
+        match (date, time) {
+            (Some(date), Some(time)) => {
+                self.set_time(date.and_time(time));
+                self.dirty = true;
+            }
+            _ => println!("Ignoring invalid RTC date/time: {:02x?}", self.time),
+        }
+    }
+
+    pub fn save_state(&self) -> [u8; RTC_STATE_SIZE] {
+        let mut state = [0u8; RTC_STATE_SIZE];
+        state[0..4].copy_from_slice(RTC_STATE_MAGIC);
+        state[4] = self.control;
+        state[8..16].copy_from_slice(&self.offset_secs.to_le_bytes());
+        state
+    }
+
+    pub fn is_state(state: &[u8]) -> bool {
+        state.len() == RTC_STATE_SIZE && state.starts_with(RTC_STATE_MAGIC)
+    }
+
+    /// Restores state written by `save_state`; anything else is ignored.
+    pub fn load_state(&mut self, state: &[u8]) {
+        if Self::is_state(state) {
+            self.control = state[4];
+            self.offset_secs = i64::from_le_bytes(state[8..16].try_into().unwrap());
+        }
+    }
+}
+
+fn to_bcd(value: u32) -> u8 {
+    (((value / 10) << 4) | (value % 10)) as u8
+}
+
+fn from_bcd(value: u8) -> u32 {
+    ((value >> 4) * 10 + (value & 0x0f)) as u32
+}
@@ -0,0 +1,153 @@
+/// Host-side readings fed to the cartridge sensors.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SensorInput {
+    /// Sunlight reaching the solar sensor, from 0 (dark) to 255 (full sun).
+    pub light_level: u8,
+    /// Tilt along each axis, from -1.0 to 1.0.
+    pub tilt_x: f32,
+    pub tilt_y: f32,
+    /// Rotation rate around the axis facing the player, from -1.0 to 1.0.
+    pub gyro_rate: f32,
+}
+
+/// Boktai's photodiode. The game resets a counter and clocks it until the comparator
+///   output goes high; the brighter the light, the sooner that happens.
+#[derive(Default)]
+pub struct SolarSensor {
+    counter: u8,
+    threshold: u8,
+    last_pins: u8,
+}
+
+impl SolarSensor {
+    const PIN_CLOCK: u8 = 0b0001;
+    const PIN_RESET: u8 = 0b0010;
+    const PIN_CS: u8 = 0b0100;
+    const PIN_FLAG: u8 = 0b1000;
+
+    pub fn write_pins(&mut self, pins: u8, input: &SensorInput) {
+        let rising_clock = (pins & Self::PIN_CLOCK) != 0 && (self.last_pins & Self::PIN_CLOCK) == 0;
+        self.last_pins = pins;
+
+        // Chip select belongs to the RTC sharing the port
+        if pins & Self::PIN_CS != 0 {
+            return;
+        }
+        if pins & Self::PIN_RESET != 0 {
+            self.counter = 0;
+            self.threshold = 0xff - input.light_level;
+        }
+        if rising_clock {
+            self.counter = self.counter.wrapping_add(1);
+        }
+    }
+
+    pub fn output_pins(&self) -> u8 {
+        if self.counter >= self.threshold {
+            Self::PIN_FLAG
+        } else {
+            0
+        }
+    }
+}
+
+/// WarioWare: Twisted!'s gyroscope. Raising pin 0 samples the rotation rate, which is then
+///   shifted out MSB first on pin 2 at each falling edge of the clock on pin 1.
+#[derive(Default)]
+pub struct GyroSensor {
+    sample: u16,
+    last_pins: u8,
+}
+
+impl GyroSensor {
+    const PIN_SAMPLE: u8 = 0b0001;
+    const PIN_CLOCK: u8 = 0b0010;
+    const PIN_DATA: u8 = 0b0100;
+    // The 12-bit reading rests near 0x6C0 when the console is held still
+    const CENTER: f32 = 0x6c0 as f32;
+    const RANGE: f32 = 0x500 as f32;
+
+    pub fn write_pins(&mut self, pins: u8, input: &SensorInput) {
+        let falling_clock =
+            (pins & Self::PIN_CLOCK) == 0 && (self.last_pins & Self::PIN_CLOCK) != 0;
+        self.last_pins = pins;
+
+        if pins & Self::PIN_SAMPLE != 0 {
+            let reading = Self::CENTER + input.gyro_rate.clamp(-1.0, 1.0) * Self::RANGE;
+            // Left aligned so the first bit shifted out is the MSB of the 12-bit value
+            self.sample = (reading as u16) << 4;
+        }
+        if falling_clock {
+            self.sample <<= 1;
+        }
+    }
+
+    pub fn output_pins(&self) -> u8 {
+        if self.sample & 0x8000 != 0 {
+            Self::PIN_DATA
+        } else {
+            0
+        }
+    }
+}
+
+/// The rumble motor, switched directly by pin 3.
+#[derive(Default)]
+pub struct Rumble {
+    active: bool,
+}
+
+impl Rumble {
+    const PIN_MOTOR: u8 = 0b1000;
+
+    pub fn write_pins(&mut self, pins: u8) {
+        self.active = pins & Self::PIN_MOTOR != 0;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+/// The two-axis accelerometer of Yoshi Topsy-Turvy and Koro Koro Puzzle. It is not on the
+///   GPIO port but answers in the 0x0E008000 region that would otherwise hold SRAM.
+#[derive(Default)]
+pub struct TiltSensor {
+    armed: bool,
+    x: u16,
+    y: u16,
+}
+
+impl TiltSensor {
+    // The 12-bit readings rest near 0x3A0 when the console is held flat
+    const CENTER: f32 = 0x3a0 as f32;
+    const RANGE: f32 = 0x200 as f32;
+
+    pub fn is_tilt_address(address: u32) -> bool {
+        (0x8000..0x8600).contains(&(address & 0xffff))
+    }
+
+    pub fn read(&self, address: u32) -> u8 {
+        match address & 0xffff {
+            0x8200 => self.x as u8,
+            // Bit 7 flags a completed sample
+            0x8300 => ((self.x >> 8) & 0xf) as u8 | 0x80,
+            0x8400 => self.y as u8,
+            0x8500 => ((self.y >> 8) & 0xf) as u8,
+            _ => 0xff,
+        }
+    }
+
+    /// Writing 0x55 to 0x0E008000 then 0xAA to 0x0E008100 latches a new sample.
+    pub fn write(&mut self, address: u32, value: u8, input: &SensorInput) {
+        match (address & 0xffff, value) {
+            (0x8000, 0x55) => self.armed = true,
+            (0x8100, 0xaa) if self.armed => {
+                self.armed = false;
+                self.x = (Self::CENTER + input.tilt_x.clamp(-1.0, 1.0) * Self::RANGE) as u16;
+                self.y = (Self::CENTER + input.tilt_y.clamp(-1.0, 1.0) * Self::RANGE) as u16;
+            }
+            _ => {}
+        }
+    }
+}
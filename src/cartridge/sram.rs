@@ -0,0 +1,39 @@
+pub const SRAM_SIZE: usize = 32 * 1024;
+
+/// Battery-backed static RAM, mirrored across the whole 0x0E000000 region.
+pub struct Sram {
+    data: Vec<u8>,
+    dirty: bool,
+}
+
+impl Default for Sram {
+    fn default() -> Self {
+        Self {
+            data: vec![0xffu8; SRAM_SIZE],
+            dirty: false,
+        }
+    }
+}
+
+impl Sram {
+    pub fn read(&self, address: u32) -> u8 {
+        self.data[(address as usize) & (SRAM_SIZE - 1)]
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) {
+        self.data[(address as usize) & (SRAM_SIZE - 1)] = value;
+        self.dirty = true;
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+}
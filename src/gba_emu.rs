@@ -1,14 +1,24 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::arm7tdmi::{self, Arm7TDMI};
+use crate::apu::{Apu, ChannelState, SoundDebug, CHANNEL_NAMES, NUM_CHANNELS};
+use crate::arm7tdmi::Arm7TDMI;
+use crate::audio;
+use crate::cartridge::{BackupType, FlashChip, RtcClock, SensorInput};
+use crate::io::{IoRegister, IO_REGISTERS};
+use crate::memory::Memory;
+use crate::ppu::RenderDebug;
+
+/// Seconds the game must leave its backup memory alone before the battery save is flushed.
+const SAVE_IDLE_SECS: f64 = 1.0;
 
 pub struct Gbaemu {
     rompath: PathBuf,
     rombytes: Vec<u8>,
     biosrombytes: Vec<u8>,
     status_bar: String,
+    last_save_write: Option<f64>,
 
-    arm_core: arm7tdmi::Arm7TDMI,
+    arm_core: Arm7TDMI<Memory>,
 }
 
 impl Default for Gbaemu {
@@ -19,6 +29,7 @@ impl Default for Gbaemu {
             biosrombytes: vec![],
 
             status_bar: String::new(),
+            last_save_write: None,
 
             arm_core: Arm7TDMI::default(),
         }
@@ -34,18 +45,74 @@ impl Gbaemu {
             self.arm_core.get_cpsr(),
             rompath
         );
+        self.last_save_write = None;
+        self.arm_core.bus.load_rom(&self.rombytes)?;
+
+        if let Some(header) = self.arm_core.bus.cartridge.header() {
+            self.status_bar
+                .push_str(format!(" ({} / {})", header.title, header.game_code).as_str());
+        }
         Ok(())
     }
 
+    pub fn rompath(&self) -> &Path {
+        &self.rompath
+    }
+
+    pub fn save_data(&self) -> Vec<u8> {
+        self.arm_core.bus.cartridge.save_data().to_owned()
+    }
+
+    /// The save as a raw backup dump, without the RTC state `save_data` keeps.
+    pub fn export_save_data(&self) -> Vec<u8> {
+        self.arm_core.bus.cartridge.backup_data().to_owned()
+    }
+
+    pub fn import_save(&mut self, savepath: String, savebytes: &[u8]) -> Result<(), &'static str> {
+        self.status_bar = format!(
+            "{:04x} | Importing save file: \"{}\"",
+            self.arm_core.get_cpsr(),
+            savepath
+        );
+        self.arm_core.bus.cartridge.import_save(savebytes)
+    }
+
+    /// Returns the battery save once the game has stopped writing to it for a while.
+    ///   `now` is any monotonic time in seconds.
+    pub fn poll_idle_save(&mut self, now: f64) -> Option<Vec<u8>> {
+        if self.arm_core.bus.cartridge.take_save_dirty() {
+            self.last_save_write = Some(now);
+        }
+        match self.last_save_write {
+            Some(last_write) if now - last_write >= SAVE_IDLE_SECS => {
+                self.last_save_write = None;
+                Some(self.save_data())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the battery save if it changed since it was last flushed, regardless of idle time.
+    pub fn take_pending_save(&mut self) -> Option<Vec<u8>> {
+        let dirty = self.arm_core.bus.cartridge.take_save_dirty();
+        if dirty || self.last_save_write.take().is_some() {
+            return Some(self.save_data());
+        }
+        None
+    }
+
+    pub fn has_pending_save(&self) -> bool {
+        self.last_save_write.is_some()
+    }
+
     pub fn load_bios_rom(&mut self, rompath: String, rombytes: &[u8]) -> Result<(), &'static str> {
-        self.rompath = PathBuf::from(rompath.clone());
         self.biosrombytes = rombytes.to_owned();
         self.status_bar = format!(
             "{:04x} | Loading bios file: \"{}\"",
             self.arm_core.get_cpsr(),
             rompath
         );
-        self.arm_core.load_bios_rom(&self.biosrombytes)
+        self.arm_core.bus.load_bios_rom(&self.biosrombytes)
     }
 
     pub fn reset(&mut self) {
@@ -58,7 +125,99 @@ impl Gbaemu {
             unimplemented!()
         } // TODO: Add support for running multiple cycles at once
 
-        self.arm_core.tick_clock(num_ticks)
+        let start_cycle = self.arm_core.clock_cycle;
+        self.arm_core.tick_clock(num_ticks)?;
+        let cycles = self.arm_core.clock_cycle - start_cycle;
+        self.arm_core.bus.step(cycles);
+        Ok(())
+    }
+
+    /// The last frame the PPU completed, as 240x160 BGR555 colors.
+    pub fn frame(&self) -> &[u16] {
+        self.arm_core.bus.ppu.frame()
+    }
+
+    /// The frame completed before `frame`, in the same format.
+    pub fn previous_frame(&self) -> &[u16] {
+        self.arm_core.bus.ppu.previous_frame()
+    }
+
+    /// The layer switches used while drawing the next frames.
+    pub fn render_debug_mut(&mut self) -> &mut RenderDebug {
+        &mut self.arm_core.bus.ppu.debug
+    }
+
+    /// The left and right audio samples produced since the last call, at `audio_sample_rate`.
+    pub fn take_audio_samples(&mut self) -> Vec<[i16; 2]> {
+        self.arm_core.bus.apu.take_samples()
+    }
+
+    pub fn audio_sample_rate(&self) -> u32 {
+        Apu::sample_rate(&self.arm_core.bus.io)
+    }
+
+    /// Starts recording the audio output at the next frame boundary, at the emulated sample
+    ///   rate. `separate_channels` also records each channel on its own.
+    pub fn start_audio_recording(&mut self, separate_channels: bool) {
+        self.arm_core.bus.apu.start_recording(separate_channels)
+    }
+
+    /// Stops recording, after which `take_audio_recording` has it. While frames are
+    ///   `running` the recording ends at the next frame boundary, else it ends straight away.
+    pub fn stop_audio_recording(&mut self, running: bool) {
+        self.arm_core.bus.apu.stop_recording(running)
+    }
+
+    pub fn is_recording_audio(&self) -> bool {
+        self.arm_core.bus.apu.is_recording()
+    }
+
+    /// A finished recording as named WAV files: the mixed output, then each channel if they
+    ///   were recorded separately.
+    pub fn take_audio_recording(&mut self) -> Option<Vec<(&'static str, Vec<u8>)>> {
+        let recording = self.arm_core.bus.apu.take_recording()?;
+        let rate = recording.sample_rate;
+        let mut files = vec![("Mixed", audio::encode_wav(rate, &recording.mixed))];
+        if let Some(samples) = &recording.channels {
+            for (channel, name) in CHANNEL_NAMES.into_iter().enumerate() {
+                let channel_samples: Vec<[i16; 2]> =
+                    samples.iter().map(|frame| frame[channel]).collect();
+                files.push((name, audio::encode_wav(rate, &channel_samples)));
+            }
+        }
+        Some(files)
+    }
+
+    /// The channel switches used while mixing the next samples.
+    pub fn sound_debug_mut(&mut self) -> &mut SoundDebug {
+        &mut self.arm_core.bus.apu.debug
+    }
+
+    pub fn sound_channels(&self) -> [ChannelState; NUM_CHANNELS] {
+        let bus = &self.arm_core.bus;
+        let timer_rates = [0, 1].map(|timer| bus.timers.overflow_rate(timer));
+        bus.apu.channel_states(&bus.io, timer_rates)
+    }
+
+    /// The recent output of `channel`, oldest first, from -1 to 1.
+    pub fn sound_scope(&self, channel: usize) -> Vec<f32> {
+        self.arm_core.bus.apu.scope(channel)
+    }
+
+    /// Both banks of wave RAM, 32 4-bit samples each, and the bank played first.
+    pub fn wave_ram(&self) -> ([[u8; 16]; 2], usize) {
+        let bus = &self.arm_core.bus;
+        (bus.apu.wave_banks(&bus.io), bus.apu.wave.bank)
+    }
+
+    pub fn set_wave_ram(&mut self, bank: usize, bytes: [u8; 16]) {
+        let bus = &mut self.arm_core.bus;
+        bus.apu.write_wave_bank(&mut bus.io, bank, bytes)
+    }
+
+    /// Frames the PPU has completed, which tells apart successive results of `frame`.
+    pub fn frame_count(&self) -> u64 {
+        self.arm_core.bus.ppu.frame_count()
     }
 
     pub fn get_status(&self) -> String {
@@ -66,18 +225,100 @@ impl Gbaemu {
     }
 
     pub fn get_core_state(&self) -> String {
-        self.arm_core.print_state()
+        let mut state = self.arm_core.print_state();
+        state.push_str(self.arm_core.bus.print_memory(64).as_str());
+        state
     }
 
     pub fn get_execution_state(&self) -> String {
         self.arm_core.print_exec_state()
     }
 
+    pub fn detected_backup_type(&self) -> BackupType {
+        self.arm_core.bus.cartridge.detected_backup_type()
+    }
+
+    pub fn backup_override(&self) -> Option<BackupType> {
+        self.arm_core.bus.cartridge.backup_override()
+    }
+
+    pub fn set_backup_override(&mut self, backup_override: Option<BackupType>) {
+        self.arm_core
+            .bus
+            .cartridge
+            .set_backup_override(backup_override)
+    }
+
+    pub fn flash_chip(&self) -> Option<FlashChip> {
+        self.arm_core.bus.cartridge.flash_chip()
+    }
+
+    pub fn set_flash_chip(&mut self, flash_chip: Option<FlashChip>) {
+        self.arm_core.bus.cartridge.set_flash_chip(flash_chip)
+    }
+
+    pub fn detected_rtc(&self) -> bool {
+        self.arm_core.bus.cartridge.detected_rtc()
+    }
+
+    pub fn rtc_override(&self) -> Option<bool> {
+        self.arm_core.bus.cartridge.rtc_override()
+    }
+
+    pub fn set_rtc_override(&mut self, rtc_override: Option<bool>) {
+        self.arm_core.bus.cartridge.set_rtc_override(rtc_override)
+    }
+
+    pub fn rtc_clock(&self) -> RtcClock {
+        self.arm_core.bus.cartridge.rtc_clock()
+    }
+
+    pub fn set_rtc_clock(&mut self, rtc_clock: RtcClock) {
+        self.arm_core.bus.cartridge.set_rtc_clock(rtc_clock)
+    }
+
+    pub fn has_solar_sensor(&self) -> bool {
+        self.arm_core.bus.cartridge.has_solar_sensor()
+    }
+
+    pub fn has_tilt_sensor(&self) -> bool {
+        self.arm_core.bus.cartridge.has_tilt_sensor()
+    }
+
+    pub fn has_gyro_sensor(&self) -> bool {
+        self.arm_core.bus.cartridge.has_gyro_sensor()
+    }
+
+    pub fn has_rumble(&self) -> bool {
+        self.arm_core.bus.cartridge.has_rumble()
+    }
+
+    pub fn rumble_active(&self) -> bool {
+        self.arm_core.bus.cartridge.rumble_active()
+    }
+
+    pub fn sensor_input(&self) -> SensorInput {
+        self.arm_core.bus.cartridge.sensor_input()
+    }
+
+    pub fn set_sensor_input(&mut self, sensor_input: SensorInput) {
+        self.arm_core.bus.cartridge.set_sensor_input(sensor_input)
+    }
+
+    /// Every IO register along with its stored contents, for the register inspector.
+    pub fn io_registers(&self) -> Vec<(&'static IoRegister, u32)> {
+        let io = &self.arm_core.bus.io;
+        IO_REGISTERS
+            .iter()
+            .map(|register| (register, io.get(register.address)))
+            .collect()
+    }
+
     pub fn advance_mem_cursor(&mut self) {
-        self.arm_core.memory.advance_mem_cursor()
+        self.arm_core.bus.advance_mem_cursor()
     }
 
     pub fn regress_mem_cursor(&mut self) {
-        self.arm_core.memory.regress_mem_cursor()
+        self.arm_core.bus.regress_mem_cursor()
     }
 }
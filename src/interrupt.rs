@@ -0,0 +1,36 @@
+use crate::io::{IoRegisters, IE, IF, IME};
+
+/// Interrupt sources, numbered by their bit in IE and IF.
+#[allow(dead_code)] // Serial, keypad and Game Pak sources arrive with their peripherals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    VBlank = 0,
+    HBlank = 1,
+    VCount = 2,
+    Timer0 = 3,
+    Timer1 = 4,
+    Timer2 = 5,
+    Timer3 = 6,
+    Serial = 7,
+    Dma0 = 8,
+    Dma1 = 9,
+    Dma2 = 10,
+    Dma3 = 11,
+    Keypad = 12,
+    GamePak = 13,
+}
+
+/// Flags `interrupt` in IF. Whether it reaches the CPU is up to IE and IME.
+pub fn request<C>(io: &mut IoRegisters<C>, interrupt: Interrupt) {
+    io.set(IF, io.get(IF) | (1 << interrupt as u32));
+}
+
+/// Whether the interrupt controller is driving the CPU's IRQ line.
+pub fn irq_line<C>(io: &IoRegisters<C>) -> bool {
+    io.get(IME) & 1 != 0 && io.get(IE) & io.get(IF) & 0x3fff != 0
+}
+
+/// Games acknowledge interrupts by writing 1s to their IF bits.
+pub fn register_io_hooks<C>(io: &mut IoRegisters<C>) {
+    io.on_write(IF, |_, write| write.old & !(write.value & write.lanes));
+}
@@ -0,0 +1,298 @@
+mod registers;
+
+pub use registers::*;
+
+pub const IO_START: u32 = 0x04000000;
+/// Size of the IO region; only its first 0x3FF bytes hold registers.
+pub const IO_SIZE: usize = 0x400;
+
+/// A CPU write as seen by a register's write hook.
+#[derive(Debug, Clone, Copy)]
+pub struct IoWrite {
+    /// Register contents before the write.
+    pub old: u32,
+    /// Register contents after the write, with writable bits updated from the bytes written.
+    pub value: u32,
+    /// Bits covered by the write, for registers whose bytes must not be touched by partial
+    ///   writes (like IF, which acknowledges interrupts by writing 1s).
+    pub lanes: u32,
+}
+
+/// Computes what a read returns instead of the stored register contents.
+pub type ReadHook<C> = fn(&C) -> u32;
+/// Reacts to a write, returning the value to store.
+pub type WriteHook<C> = fn(&mut C, IoWrite) -> u32;
+
+struct IoHooks<C> {
+    read: Option<ReadHook<C>>,
+    write: Option<WriteHook<C>>,
+}
+
+pub fn is_io_address(address: u32) -> bool {
+    (IO_START..IO_START + IO_SIZE as u32).contains(&address)
+}
+
+/// The register file behind the IO region, along with the hooks peripherals registered on
+///   it. `C` is the context hooks get access to, usually the memory bus owning the table.
+pub struct IoRegisters<C> {
+    data: [u8; IO_SIZE],
+    // Index into `IO_REGISTERS` of the register each byte belongs to
+    lookup: [Option<u8>; IO_SIZE],
+    hooks: Vec<IoHooks<C>>,
+}
+
+impl<C> Default for IoRegisters<C> {
+    fn default() -> Self {
+        let mut lookup = [None; IO_SIZE];
+        for (index, register) in IO_REGISTERS.iter().enumerate() {
+            let offset = (register.address - IO_START) as usize;
+            for entry in &mut lookup[offset..offset + register.size as usize] {
+                *entry = Some(index as u8);
+            }
+        }
+
+        let mut registers = Self {
+            data: [0u8; IO_SIZE],
+            lookup,
+            hooks: IO_REGISTERS
+                .iter()
+                .map(|_| IoHooks {
+                    read: None,
+                    write: None,
+                })
+                .collect(),
+        };
+        for register in IO_REGISTERS {
+            registers.set(register.address, register.reset);
+        }
+        registers
+    }
+}
+
+impl<C> IoRegisters<C> {
+    fn index(&self, address: u32) -> Option<usize> {
+        let offset = address.checked_sub(IO_START)? as usize;
+        self.lookup.get(offset).copied().flatten().map(usize::from)
+    }
+
+    /// The register covering `address`, if any.
+    pub fn descriptor(&self, address: u32) -> Option<&'static IoRegister> {
+        self.index(address).map(|index| &IO_REGISTERS[index])
+    }
+
+    fn index_of(&self, address: u32) -> usize {
+        match self.index(address) {
+            Some(index) if IO_REGISTERS[index].address == address => index,
+            _ => panic!("No IO register at {:#010x}", address),
+        }
+    }
+
+    pub fn on_read(&mut self, address: u32, hook: ReadHook<C>) {
+        let index = self.index_of(address);
+        self.hooks[index].read = Some(hook);
+    }
+
+    pub fn on_write(&mut self, address: u32, hook: WriteHook<C>) {
+        let index = self.index_of(address);
+        self.hooks[index].write = Some(hook);
+    }
+
+    /// The stored contents of the register at `address`, bypassing masks and hooks.
+    pub fn get(&self, address: u32) -> u32 {
+        let register = &IO_REGISTERS[self.index_of(address)];
+        let offset = (address - IO_START) as usize;
+        self.data[offset..offset + register.size as usize]
+            .iter()
+            .rev()
+            .fold(0, |value, byte| (value << 8) | *byte as u32)
+    }
+
+    /// Updates the stored contents of the register at `address`, bypassing masks and hooks.
+    ///   Peripherals use it to report status bits the CPU can't write.
+    pub fn set(&mut self, address: u32, value: u32) {
+        let register = &IO_REGISTERS[self.index_of(address)];
+        let offset = (address - IO_START) as usize;
+        for (byte_index, byte) in self.data[offset..offset + register.size as usize]
+            .iter_mut()
+            .enumerate()
+        {
+            *byte = (value >> (byte_index * 8)) as u8;
+        }
+    }
+}
+
+/// Implemented by whatever owns the `IoRegisters`, so hooks can reach the peripherals.
+pub trait IoContext: Sized {
+    fn io(&self) -> &IoRegisters<Self>;
+    fn io_mut(&mut self) -> &mut IoRegisters<Self>;
+}
+
+/// Splits an access of `num_bytes` at `address` into the registers it touches, calling
+///   `access` with each register, the byte offset of the access within the register, the
+///   byte offset of the register within the access and the number of bytes shared.
+fn for_each_register(
+    lookup: impl Fn(u32) -> Option<&'static IoRegister>,
+    address: u32,
+    num_bytes: u32,
+    mut access: impl FnMut(&'static IoRegister, u32, u32, u32),
+) {
+    let mut offset = 0;
+    while offset < num_bytes {
+        match lookup(address + offset) {
+            // Unused addresses read as zero and ignore writes
+            None => offset += 1,
+            Some(register) => {
+                let register_offset = address + offset - register.address;
+                let span = (register.size - register_offset).min(num_bytes - offset);
+                access(register, register_offset, offset, span);
+                offset += span;
+            }
+        }
+    }
+}
+
+fn lane_mask(register_offset: u32, span: u32) -> u32 {
+    (((1u64 << (span * 8)) - 1) << (register_offset * 8)) as u32
+}
+
+/// Reads `num_bytes` (1, 2 or 4) from the IO region, as the CPU would.
+pub fn read<C: IoContext>(context: &C, address: u32, num_bytes: u32) -> u32 {
+    let io = context.io();
+    let mut result = 0u32;
+    for_each_register(
+        |address| io.descriptor(address),
+        address,
+        num_bytes,
+        |register, register_offset, offset, span| {
+            let index = io.index_of(register.address);
+            let value = match io.hooks[index].read {
+                Some(hook) => hook(context),
+                None => io.get(register.address),
+            } & register.read_mask;
+            let bytes = (value & lane_mask(register_offset, span)) >> (register_offset * 8);
+            result |= bytes << (offset * 8);
+        },
+    );
+    result
+}
+
+/// Writes the low `num_bytes` (1, 2 or 4) of `value` to the IO region, as the CPU would.
+pub fn write<C: IoContext>(context: &mut C, address: u32, value: u32, num_bytes: u32) {
+    let mut accesses = Vec::with_capacity(2);
+    for_each_register(
+        |address| context.io().descriptor(address),
+        address,
+        num_bytes,
+        |register, register_offset, offset, span| {
+            accesses.push((register, register_offset, offset, span));
+        },
+    );
+
+    for (register, register_offset, offset, span) in accesses {
+        let lanes = lane_mask(register_offset, span);
+        let written = ((value >> (offset * 8)) << (register_offset * 8)) & lanes;
+        let old = context.io().get(register.address);
+        let merged = (old & !lanes) | written;
+        let write = IoWrite {
+            old,
+            value: (old & !register.write_mask) | (merged & register.write_mask),
+            lanes,
+        };
+
+        let index = context.io().index_of(register.address);
+        let stored = match context.io().hooks[index].write {
+            Some(hook) => hook(context, write),
+            None => write.value,
+        };
+        context.io_mut().set(register.address, stored);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupt;
+
+    /// A context with the interrupt hooks, that logs the lanes of each BG0CNT write.
+    struct TestContext {
+        io: IoRegisters<Self>,
+        bg0cnt_lanes: Vec<u32>,
+    }
+
+    impl IoContext for TestContext {
+        fn io(&self) -> &IoRegisters<Self> {
+            &self.io
+        }
+
+        fn io_mut(&mut self) -> &mut IoRegisters<Self> {
+            &mut self.io
+        }
+    }
+
+    impl Default for TestContext {
+        fn default() -> Self {
+            let mut context = Self {
+                io: IoRegisters::default(),
+                bg0cnt_lanes: Vec::new(),
+            };
+            interrupt::register_io_hooks(&mut context.io);
+            context.io.on_write(BG0CNT, |context, write| {
+                context.bg0cnt_lanes.push(write.lanes);
+                write.value
+            });
+            context
+        }
+    }
+
+    #[test]
+    fn byte_writes_only_change_their_lane() {
+        let mut context = TestContext::default();
+        write(&mut context, BG0CNT, 0x1234, 2);
+        write(&mut context, BG0CNT + 1, 0xff, 1);
+        // Bit 13 of BG0CNT isn't writable
+        assert_eq!(context.io.get(BG0CNT), 0xdf34);
+        assert_eq!(read(&context, BG0CNT + 1, 1), 0xdf);
+        assert_eq!(context.bg0cnt_lanes, vec![0xffff, 0xff00]);
+    }
+
+    #[test]
+    fn write_only_registers_read_as_zero() {
+        let mut context = TestContext::default();
+        write(&mut context, BG0HOFS, 0x01ff, 2);
+        assert_eq!(context.io.get(BG0HOFS), 0x01ff);
+        assert_eq!(read(&context, BG0HOFS, 2), 0);
+    }
+
+    #[test]
+    fn word_accesses_span_two_registers() {
+        let mut context = TestContext::default();
+        write(&mut context, BG0CNT, 0x0102_0304, 4);
+        assert_eq!(context.io.get(BG0CNT), 0x0304);
+        assert_eq!(context.io.get(BG0CNT + 2), 0x0102);
+        assert_eq!(read(&context, BG0CNT, 4), 0x0102_0304);
+        assert_eq!(context.bg0cnt_lanes, vec![0xffff]);
+
+        // The unused halfword after WAITCNT reads as zero
+        write(&mut context, WAITCNT, 0xffff_ffff, 4);
+        assert_eq!(read(&context, WAITCNT, 4), 0x5fff);
+    }
+
+    #[test]
+    fn if_writes_acknowledge_only_their_lanes() {
+        let mut context = TestContext::default();
+        context.io.set(IF, 0x0107);
+        // Only the 1s written clear their flags; the untouched byte keeps its flags
+        write(&mut context, IF, 0x02, 1);
+        assert_eq!(context.io.get(IF), 0x0105);
+        write(&mut context, IF + 1, 0x01, 1);
+        assert_eq!(context.io.get(IF), 0x0005);
+        write(&mut context, IE, 0x0005_0000, 4);
+        assert_eq!(context.io.get(IF), 0);
+    }
+
+    #[test]
+    fn keyinput_starts_with_no_keys_held() {
+        let context = TestContext::default();
+        assert_eq!(read(&context, KEYINPUT, 2), 0x03ff);
+    }
+}
@@ -0,0 +1,211 @@
+/// Describes one memory mapped register in the 0x04000000 IO region.
+#[derive(Debug)]
+pub struct IoRegister {
+    pub name: &'static str,
+    pub address: u32,
+    /// Width in bytes: 1, 2 or 4.
+    pub size: u32,
+    /// Bits that read back; zero for write-only registers.
+    pub read_mask: u32,
+    /// Bits the CPU can change; zero for read-only registers.
+    pub write_mask: u32,
+    /// Contents at power on.
+    pub reset: u32,
+}
+
+impl IoRegister {
+    pub fn is_readable(&self) -> bool {
+        self.read_mask != 0
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.write_mask != 0
+    }
+
+    /// Starts the register at `value` rather than zero.
+    const fn reset_to(self, value: u32) -> Self {
+        IoRegister {
+            reset: value,
+            ..self
+        }
+    }
+}
+
+const fn reg(name: &'static str, address: u32, size: u32, read: u32, write: u32) -> IoRegister {
+    IoRegister {
+        name,
+        address,
+        size,
+        read_mask: read,
+        write_mask: write,
+        reset: 0,
+    }
+}
+
+pub const DISPCNT: u32 = 0x04000000;
+pub const DISPSTAT: u32 = 0x04000004;
+pub const VCOUNT: u32 = 0x04000006;
+/// BG0's control and scroll registers; those of BG1-BG3 follow at 2 and 4 byte strides.
+pub const BG0CNT: u32 = 0x04000008;
+pub const BG0HOFS: u32 = 0x04000010;
+/// BG2's affine parameters; BG3's follow 0x10 bytes later.
+pub const BG2PA: u32 = 0x04000020;
+pub const BG2X: u32 = 0x04000028;
+pub const BG2Y: u32 = 0x0400002c;
+pub const BG3X: u32 = 0x04000038;
+pub const BG3Y: u32 = 0x0400003c;
+/// WIN0H, followed by WIN1H, WIN0V and WIN1V.
+pub const WIN0H: u32 = 0x04000040;
+pub const WININ: u32 = 0x04000048;
+pub const WINOUT: u32 = 0x0400004a;
+pub const MOSAIC: u32 = 0x0400004c;
+pub const BLDCNT: u32 = 0x04000050;
+pub const BLDALPHA: u32 = 0x04000052;
+pub const BLDY: u32 = 0x04000054;
+pub const SOUND1CNT_L: u32 = 0x04000060;
+pub const SOUND1CNT_H: u32 = 0x04000062;
+pub const SOUND1CNT_X: u32 = 0x04000064;
+pub const SOUND2CNT_L: u32 = 0x04000068;
+pub const SOUND2CNT_H: u32 = 0x0400006c;
+pub const SOUND3CNT_L: u32 = 0x04000070;
+pub const SOUND3CNT_H: u32 = 0x04000072;
+pub const SOUND3CNT_X: u32 = 0x04000074;
+pub const SOUND4CNT_L: u32 = 0x04000078;
+pub const SOUND4CNT_H: u32 = 0x0400007c;
+pub const SOUNDCNT_L: u32 = 0x04000080;
+pub const SOUNDCNT_H: u32 = 0x04000082;
+pub const SOUNDCNT_X: u32 = 0x04000084;
+pub const SOUNDBIAS: u32 = 0x04000088;
+/// WAVE_RAM0_L, followed by the other seven halfwords of wave RAM.
+pub const WAVE_RAM: u32 = 0x04000090;
+pub const FIFO_A: u32 = 0x040000a0;
+pub const FIFO_B: u32 = 0x040000a4;
+/// DMA0's registers; those of DMA1-DMA3 follow at a 12 byte stride.
+pub const DMA0SAD: u32 = 0x040000b0;
+pub const DMA0CNT_H: u32 = 0x040000ba;
+/// Timer 0's registers; those of timers 1-3 follow at a 4 byte stride.
+pub const TM0CNT_L: u32 = 0x04000100;
+pub const TM0CNT_H: u32 = 0x04000102;
+pub const KEYINPUT: u32 = 0x04000130;
+pub const IE: u32 = 0x04000200;
+pub const IF: u32 = 0x04000202;
+pub const WAITCNT: u32 = 0x04000204;
+pub const IME: u32 = 0x04000208;
+
+/// Every register of the IO region, in address order. Addresses not listed are unused:
+///   they read as zero and ignore writes.
+pub static IO_REGISTERS: &[IoRegister] = &[
+    // LCD
+    reg("DISPCNT", DISPCNT, 2, 0xffff, 0xfff7),
+    reg("GREENSWAP", 0x04000002, 2, 0x0001, 0x0001), // Undocumented
+    reg("DISPSTAT", DISPSTAT, 2, 0xff3f, 0xff38),
+    reg("VCOUNT", VCOUNT, 2, 0x00ff, 0x0000),
+    reg("BG0CNT", BG0CNT, 2, 0xdfff, 0xdfff),
+    reg("BG1CNT", 0x0400000a, 2, 0xdfff, 0xdfff),
+    reg("BG2CNT", 0x0400000c, 2, 0xffff, 0xffff),
+    reg("BG3CNT", 0x0400000e, 2, 0xffff, 0xffff),
+    reg("BG0HOFS", BG0HOFS, 2, 0, 0x01ff),
+    reg("BG0VOFS", 0x04000012, 2, 0, 0x01ff),
+    reg("BG1HOFS", 0x04000014, 2, 0, 0x01ff),
+    reg("BG1VOFS", 0x04000016, 2, 0, 0x01ff),
+    reg("BG2HOFS", 0x04000018, 2, 0, 0x01ff),
+    reg("BG2VOFS", 0x0400001a, 2, 0, 0x01ff),
+    reg("BG3HOFS", 0x0400001c, 2, 0, 0x01ff),
+    reg("BG3VOFS", 0x0400001e, 2, 0, 0x01ff),
+    reg("BG2PA", BG2PA, 2, 0, 0xffff),
+    reg("BG2PB", 0x04000022, 2, 0, 0xffff),
+    reg("BG2PC", 0x04000024, 2, 0, 0xffff),
+    reg("BG2PD", 0x04000026, 2, 0, 0xffff),
+    reg("BG2X", BG2X, 4, 0, 0x0fffffff),
+    reg("BG2Y", BG2Y, 4, 0, 0x0fffffff),
+    reg("BG3PA", 0x04000030, 2, 0, 0xffff),
+    reg("BG3PB", 0x04000032, 2, 0, 0xffff),
+    reg("BG3PC", 0x04000034, 2, 0, 0xffff),
+    reg("BG3PD", 0x04000036, 2, 0, 0xffff),
+    reg("BG3X", BG3X, 4, 0, 0x0fffffff),
+    reg("BG3Y", BG3Y, 4, 0, 0x0fffffff),
+    reg("WIN0H", WIN0H, 2, 0, 0xffff),
+    reg("WIN1H", 0x04000042, 2, 0, 0xffff),
+    reg("WIN0V", 0x04000044, 2, 0, 0xffff),
+    reg("WIN1V", 0x04000046, 2, 0, 0xffff),
+    reg("WININ", WININ, 2, 0x3f3f, 0x3f3f),
+    reg("WINOUT", WINOUT, 2, 0x3f3f, 0x3f3f),
+    reg("MOSAIC", MOSAIC, 2, 0, 0xffff),
+    reg("BLDCNT", BLDCNT, 2, 0x3fff, 0x3fff),
+    reg("BLDALPHA", BLDALPHA, 2, 0x1f1f, 0x1f1f),
+    reg("BLDY", BLDY, 2, 0, 0x001f),
+    // Sound
+    reg("SOUND1CNT_L", 0x04000060, 2, 0x007f, 0x007f),
+    reg("SOUND1CNT_H", 0x04000062, 2, 0xffc0, 0xffff),
+    reg("SOUND1CNT_X", 0x04000064, 2, 0x4000, 0xc7ff),
+    reg("SOUND2CNT_L", 0x04000068, 2, 0xffc0, 0xffff),
+    reg("SOUND2CNT_H", 0x0400006c, 2, 0x4000, 0xc7ff),
+    reg("SOUND3CNT_L", 0x04000070, 2, 0x00e0, 0x00e0),
+    reg("SOUND3CNT_H", 0x04000072, 2, 0xe000, 0xe0ff),
+    reg("SOUND3CNT_X", 0x04000074, 2, 0x4000, 0xc7ff),
+    reg("SOUND4CNT_L", 0x04000078, 2, 0xff00, 0xff3f),
+    reg("SOUND4CNT_H", 0x0400007c, 2, 0x40ff, 0xc0ff),
+    reg("SOUNDCNT_L", 0x04000080, 2, 0xff77, 0xff77),
+    reg("SOUNDCNT_H", 0x04000082, 2, 0x770f, 0xff0f),
+    reg("SOUNDCNT_X", 0x04000084, 2, 0x008f, 0x0080),
+    reg("SOUNDBIAS", 0x04000088, 2, 0xc3fe, 0xc3fe),
+    reg("WAVE_RAM0_L", 0x04000090, 2, 0xffff, 0xffff),
+    reg("WAVE_RAM0_H", 0x04000092, 2, 0xffff, 0xffff),
+    reg("WAVE_RAM1_L", 0x04000094, 2, 0xffff, 0xffff),
+    reg("WAVE_RAM1_H", 0x04000096, 2, 0xffff, 0xffff),
+    reg("WAVE_RAM2_L", 0x04000098, 2, 0xffff, 0xffff),
+    reg("WAVE_RAM2_H", 0x0400009a, 2, 0xffff, 0xffff),
+    reg("WAVE_RAM3_L", 0x0400009c, 2, 0xffff, 0xffff),
+    reg("WAVE_RAM3_H", 0x0400009e, 2, 0xffff, 0xffff),
+    reg("FIFO_A", 0x040000a0, 4, 0, 0xffffffff),
+    reg("FIFO_B", 0x040000a4, 4, 0, 0xffffffff),
+    // DMA
+    reg("DMA0SAD", 0x040000b0, 4, 0, 0x07ffffff),
+    reg("DMA0DAD", 0x040000b4, 4, 0, 0x07ffffff),
+    reg("DMA0CNT_L", 0x040000b8, 2, 0, 0x3fff),
+    reg("DMA0CNT_H", 0x040000ba, 2, 0xf7e0, 0xf7e0),
+    reg("DMA1SAD", 0x040000bc, 4, 0, 0x0fffffff),
+    reg("DMA1DAD", 0x040000c0, 4, 0, 0x07ffffff),
+    reg("DMA1CNT_L", 0x040000c4, 2, 0, 0x3fff),
+    reg("DMA1CNT_H", 0x040000c6, 2, 0xf7e0, 0xf7e0),
+    reg("DMA2SAD", 0x040000c8, 4, 0, 0x0fffffff),
+    reg("DMA2DAD", 0x040000cc, 4, 0, 0x07ffffff),
+    reg("DMA2CNT_L", 0x040000d0, 2, 0, 0x3fff),
+    reg("DMA2CNT_H", 0x040000d2, 2, 0xf7e0, 0xf7e0),
+    reg("DMA3SAD", 0x040000d4, 4, 0, 0x0fffffff),
+    reg("DMA3DAD", 0x040000d8, 4, 0, 0x0fffffff),
+    reg("DMA3CNT_L", 0x040000dc, 2, 0, 0xffff),
+    reg("DMA3CNT_H", 0x040000de, 2, 0xffe0, 0xffe0),
+    // Timers
+    reg("TM0CNT_L", 0x04000100, 2, 0xffff, 0xffff),
+    reg("TM0CNT_H", 0x04000102, 2, 0x00c3, 0x00c3),
+    reg("TM1CNT_L", 0x04000104, 2, 0xffff, 0xffff),
+    reg("TM1CNT_H", 0x04000106, 2, 0x00c7, 0x00c7),
+    reg("TM2CNT_L", 0x04000108, 2, 0xffff, 0xffff),
+    reg("TM2CNT_H", 0x0400010a, 2, 0x00c7, 0x00c7),
+    reg("TM3CNT_L", 0x0400010c, 2, 0xffff, 0xffff),
+    reg("TM3CNT_H", 0x0400010e, 2, 0x00c7, 0x00c7),
+    // Serial communication
+    reg("SIOMULTI0", 0x04000120, 2, 0xffff, 0xffff),
+    reg("SIOMULTI1", 0x04000122, 2, 0xffff, 0xffff),
+    reg("SIOMULTI2", 0x04000124, 2, 0xffff, 0xffff),
+    reg("SIOMULTI3", 0x04000126, 2, 0xffff, 0xffff),
+    reg("SIOCNT", 0x04000128, 2, 0x7fff, 0x7fff),
+    reg("SIOMLT_SEND", 0x0400012a, 2, 0xffff, 0xffff),
+    // Keypad
+    reg("KEYINPUT", KEYINPUT, 2, 0x03ff, 0x0000).reset_to(0x03ff), // Active low
+    reg("KEYCNT", 0x04000132, 2, 0xc3ff, 0xc3ff),
+    reg("RCNT", 0x04000134, 2, 0xc1ff, 0xc1ff),
+    reg("IR", 0x04000136, 2, 0x0000, 0x0000), // Undocumented, unused on retail units
+    reg("JOYCNT", 0x04000140, 2, 0x0047, 0x0047),
+    reg("JOY_RECV", 0x04000150, 4, 0xffffffff, 0xffffffff),
+    reg("JOY_TRANS", 0x04000154, 4, 0xffffffff, 0xffffffff),
+    reg("JOYSTAT", 0x04000158, 2, 0x003a, 0x0030),
+    // Interrupt, waitstate and power-down control
+    reg("IE", IE, 2, 0x3fff, 0x3fff),
+    reg("IF", IF, 2, 0x3fff, 0x3fff),
+    reg("WAITCNT", WAITCNT, 2, 0x5fff, 0x5fff),
+    reg("IME", IME, 2, 0x0001, 0x0001),
+    reg("POSTFLG", 0x04000300, 1, 0x01, 0x01), // Undocumented
+    reg("HALTCNT", 0x04000301, 1, 0, 0x80),    // Undocumented
+];
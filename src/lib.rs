@@ -1,7 +1,17 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
+mod apu;
 mod arm7tdmi;
+mod audio;
+mod cartridge;
 mod gba_emu;
+mod interrupt;
+mod io;
+mod memory;
+mod ppu;
+mod save_file;
+mod timer;
 mod util;
+mod video;
 pub use app::EmulatorApp;
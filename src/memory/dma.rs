@@ -0,0 +1,256 @@
+use super::Memory;
+use crate::interrupt::{self, Interrupt};
+use crate::io::{IoRegisters, IoWrite, DMA0CNT_H, DMA0SAD};
+use crate::util::get_bits;
+
+const NUM_CHANNELS: usize = 4;
+/// Each channel's SAD, DAD, CNT_L and CNT_H take 12 bytes.
+const CHANNEL_STRIDE: u32 = 0xc;
+
+const INTERRUPTS: [Interrupt; NUM_CHANNELS] = [
+    Interrupt::Dma0,
+    Interrupt::Dma1,
+    Interrupt::Dma2,
+    Interrupt::Dma3,
+];
+/// Internal memory only for DMA0, and all but the Game Pak for DMA0-2's destinations.
+const SOURCE_MASKS: [u32; NUM_CHANNELS] = [0x07ff_ffff, 0x0fff_ffff, 0x0fff_ffff, 0x0fff_ffff];
+const DEST_MASKS: [u32; NUM_CHANNELS] = [0x07ff_ffff, 0x07ff_ffff, 0x07ff_ffff, 0x0fff_ffff];
+
+const DMACNT_REPEAT: u32 = 1 << 9;
+const DMACNT_WORD: u32 = 1 << 10;
+const DMACNT_IRQ: u32 = 1 << 14;
+const DMACNT_ENABLE: u32 = 1 << 15;
+/// The destination setting that steps like increment, but restarts on every repeat.
+const INCREMENT_RELOAD: u32 = 3;
+
+/// Words moved per sound FIFO refill.
+const FIFO_TRANSFER_WORDS: u32 = 4;
+
+/// When a channel starts its transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmaTiming {
+    Immediate,
+    VBlank,
+    HBlank,
+    /// Sound FIFO refills for DMA1 and DMA2, video capture for DMA3.
+    Special,
+}
+
+/// The internal registers a channel latches when it is enabled, and keeps stepping between
+///   repeats.
+#[derive(Debug, Default, Clone, Copy)]
+struct Channel {
+    source: u32,
+    dest: u32,
+    count: u32,
+    control: u32,
+}
+
+impl Channel {
+    fn timing(&self) -> DmaTiming {
+        match get_bits(self.control, 12, 2) {
+            0 => DmaTiming::Immediate,
+            1 => DmaTiming::VBlank,
+            2 => DmaTiming::HBlank,
+            _ => DmaTiming::Special,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.control & DMACNT_ENABLE != 0
+    }
+}
+
+/// The four DMA channels. Transfers complete at once, without holding up the CPU.
+#[derive(Debug, Default)]
+pub struct Dma {
+    channels: [Channel; NUM_CHANNELS],
+    /// The last value a transfer read, which reads from unmapped addresses return again.
+    open_bus: u32,
+}
+
+impl Dma {
+    pub fn register_io_hooks(io: &mut IoRegisters<Memory>) {
+        io.on_write(DMA0CNT_H, |memory, write| {
+            memory.write_dma_control(0, write)
+        });
+        io.on_write(DMA0CNT_H + CHANNEL_STRIDE, |memory, write| {
+            memory.write_dma_control(1, write)
+        });
+        io.on_write(DMA0CNT_H + 2 * CHANNEL_STRIDE, |memory, write| {
+            memory.write_dma_control(2, write)
+        });
+        io.on_write(DMA0CNT_H + 3 * CHANNEL_STRIDE, |memory, write| {
+            memory.write_dma_control(3, write)
+        });
+    }
+}
+
+/// A count of 0 stands for the largest count: 0x4000 units, or 0x10000 for DMA3.
+fn transfer_count(index: usize, count: u32) -> u32 {
+    match (index, count) {
+        (3, 0) => 0x10000,
+        (_, 0) => 0x4000,
+        _ => count,
+    }
+}
+
+impl Memory {
+    /// Enabling a channel latches its addresses and count, and starts immediate transfers.
+    fn write_dma_control(&mut self, index: usize, write: IoWrite) -> u32 {
+        let base = DMA0SAD + index as u32 * CHANNEL_STRIDE;
+        let control_register = DMA0CNT_H + index as u32 * CHANNEL_STRIDE;
+        let starting = write.old & DMACNT_ENABLE == 0 && write.value & DMACNT_ENABLE != 0;
+        let channel = &mut self.dma.channels[index];
+        channel.control = write.value;
+        if !starting {
+            return write.value;
+        }
+
+        channel.source = self.io.get(base) & SOURCE_MASKS[index];
+        channel.dest = self.io.get(base + 4) & DEST_MASKS[index];
+        channel.count = transfer_count(index, self.io.get(base + 8));
+        if channel.timing() == DmaTiming::Immediate {
+            // The transfer may clear the enable bit, so it decides what gets stored
+            self.io.set(control_register, write.value);
+            self.run_dma(index);
+            return self.io.get(control_register);
+        }
+        write.value
+    }
+
+    /// Starts the enabled channels waiting for `timing`.
+    pub fn trigger_dma(&mut self, timing: DmaTiming) {
+        for index in 0..NUM_CHANNELS {
+            let channel = &self.dma.channels[index];
+            if channel.enabled() && channel.timing() == timing && timing != DmaTiming::Special {
+                self.run_dma(index);
+            }
+        }
+    }
+
+    /// Refills the sound FIFO at `fifo_address` with DMA1 or DMA2, whichever is set up to
+    ///   feed it.
+    pub fn request_fifo_dma(&mut self, fifo_address: u32) {
+        for index in 1..=2 {
+            let channel = &self.dma.channels[index];
+            if channel.enabled()
+                && channel.timing() == DmaTiming::Special
+                && channel.dest == fifo_address
+            {
+                self.run_dma(index);
+            }
+        }
+    }
+
+    fn run_dma(&mut self, index: usize) {
+        let channel = self.dma.channels[index];
+        let base = DMA0SAD + index as u32 * CHANNEL_STRIDE;
+        let control_register = DMA0CNT_H + index as u32 * CHANNEL_STRIDE;
+        // FIFO refills always move four words to the same address
+        let fifo = channel.timing() == DmaTiming::Special && (index == 1 || index == 2);
+        let (word, count) = if fifo {
+            (true, FIFO_TRANSFER_WORDS)
+        } else {
+            (channel.control & DMACNT_WORD != 0, channel.count)
+        };
+
+        let unit: u32 = if word { 4 } else { 2 };
+        let step = |setting: u32| match setting {
+            1 => unit.wrapping_neg(),
+            2 => 0,
+            _ => unit,
+        };
+        let source_step = step(get_bits(channel.control, 7, 2));
+        let dest_setting = get_bits(channel.control, 5, 2);
+        let dest_step = if fifo { 0 } else { step(dest_setting) };
+
+        if index == 3 {
+            self.observe_dma(
+                channel.source as usize,
+                channel.dest as usize,
+                count as usize,
+            );
+        }
+        let (mut source, mut dest) = (channel.source, channel.dest);
+        for _ in 0..count {
+            let mapped = Memory::is_mapped(source as usize);
+            if word {
+                if mapped {
+                    self.dma.open_bus = self.get_word(source as usize);
+                }
+                self.set_word(dest as usize, self.dma.open_bus);
+            } else {
+                // Halfwords are latched onto both halves of the bus
+                if mapped {
+                    self.dma.open_bus = self.get_halfword(source as usize) as u32 * 0x10001;
+                }
+                self.set_halfword(dest as usize, self.dma.open_bus as u16);
+            }
+            source = source.wrapping_add(source_step);
+            dest = dest.wrapping_add(dest_step);
+        }
+
+        let channel = &mut self.dma.channels[index];
+        channel.source = source;
+        channel.dest = dest;
+        if channel.control & DMACNT_REPEAT != 0 && channel.timing() != DmaTiming::Immediate {
+            channel.count = transfer_count(index, self.io.get(base + 8));
+            if dest_setting == INCREMENT_RELOAD {
+                channel.dest = self.io.get(base + 4) & DEST_MASKS[index];
+            }
+        } else {
+            channel.control &= !DMACNT_ENABLE;
+            self.io.set(control_register, channel.control);
+        }
+        if channel.control & DMACNT_IRQ != 0 {
+            interrupt::request(&mut self.io, INTERRUPTS[index]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DMA3SAD: usize = (DMA0SAD + 3 * CHANNEL_STRIDE) as usize;
+
+    /// Runs an immediate, incrementing DMA3 transfer.
+    fn transfer(memory: &mut Memory, source: u32, dest: u32, count: u32, word: bool) {
+        memory.set_word(DMA3SAD, source);
+        memory.set_word(DMA3SAD + 4, dest);
+        memory.set_halfword(DMA3SAD + 8, count as u16);
+        let width = if word { DMACNT_WORD } else { 0 };
+        memory.set_halfword(DMA3SAD + 10, (DMACNT_ENABLE | width) as u16);
+    }
+
+    #[test]
+    fn copies_between_work_rams() {
+        let mut memory = Memory::default();
+        for i in 0..16 {
+            memory.set_byte(0x0200_0000 + i, i as u8);
+        }
+        transfer(&mut memory, 0x0200_0000, 0x0300_0000, 4, true);
+        for i in 0..16 {
+            assert_eq!(memory.get_byte(0x0300_0000 + i), i as u8);
+        }
+        // Both RAMs are mirrored through their whole regions
+        assert_eq!(memory.get_byte(0x0304_0005), 5);
+        assert_eq!(memory.get_byte(0x0204_0005), 5);
+    }
+
+    #[test]
+    fn unmapped_reads_repeat_the_last_value_moved() {
+        let mut memory = Memory::default();
+        memory.set_halfword(0x0200_0000, 0xbeef);
+        transfer(&mut memory, 0x0200_0000, 0x0300_0000, 1, false);
+        transfer(&mut memory, 0x0100_0000, 0x0300_0000, 2, true);
+        assert_eq!(memory.get_word(0x0300_0000), 0xbeef_beef);
+        assert_eq!(memory.get_word(0x0300_0004), 0xbeef_beef);
+
+        // Writes to unmapped addresses go nowhere
+        transfer(&mut memory, 0x0300_0000, 0x0100_0000, 2, true);
+        assert_eq!(memory.get_word(0x0100_0000), 0);
+    }
+}
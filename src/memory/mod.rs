@@ -0,0 +1,307 @@
+mod dma;
+mod waitcnt;
+
+use crate::apu::Apu;
+use crate::arm7tdmi::{AccessType, AccessWidth, Bus};
+use crate::cartridge::Cartridge;
+use crate::interrupt;
+use crate::io::{self, IoContext, IoRegisters, FIFO_A, FIFO_B};
+use crate::ppu::Ppu;
+use crate::timer::Timers;
+use crate::util::get_word;
+use dma::{Dma, DmaTiming};
+use waitcnt::WaitControl;
+
+/// On-board work RAM, mirrored every 256K through 0x02000000-0x02ffffff.
+const EWRAM_SIZE: usize = 256 * 1024;
+/// On-chip work RAM, mirrored every 32K through 0x03000000-0x03ffffff.
+const IWRAM_SIZE: usize = 32 * 1024;
+
+pub struct Memory {
+    print_cursor: usize,
+    bios_rom: [u8; 16384],
+    ewram: Vec<u8>,
+    iwram: Vec<u8>,
+    pub io: IoRegisters<Memory>,
+    pub cartridge: Cartridge,
+    pub wait_control: WaitControl,
+    pub ppu: Ppu,
+    pub apu: Apu,
+    pub timers: Timers,
+    pub dma: Dma,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        let mut memory = Self {
+            print_cursor: 0usize,
+            bios_rom: [0u8; 16384],
+            ewram: vec![0u8; EWRAM_SIZE],
+            iwram: vec![0u8; IWRAM_SIZE],
+            io: IoRegisters::default(),
+            cartridge: Cartridge::default(),
+            wait_control: WaitControl::default(),
+            ppu: Ppu::default(),
+            apu: Apu::default(),
+            timers: Timers::default(),
+            dma: Dma::default(),
+        };
+        interrupt::register_io_hooks(&mut memory.io);
+        WaitControl::register_io_hooks(&mut memory.io);
+        Ppu::register_io_hooks(&mut memory.io);
+        Apu::register_io_hooks(&mut memory.io);
+        Timers::register_io_hooks(&mut memory.io);
+        Dma::register_io_hooks(&mut memory.io);
+        memory
+    }
+}
+
+impl IoContext for Memory {
+    fn io(&self) -> &IoRegisters<Self> {
+        &self.io
+    }
+
+    fn io_mut(&mut self) -> &mut IoRegisters<Self> {
+        &mut self.io
+    }
+}
+
+impl Memory {
+    pub fn load_bios_rom(&mut self, bios_bytes: &Vec<u8>) -> Result<(), &'static str> {
+        if bios_bytes.len() != 16384usize {
+            return Err("Bios image incorrect size: Expected 16384");
+        }
+
+        println!("Loading bios rom");
+        self.bios_rom.clone_from_slice(bios_bytes.as_slice());
+        Ok(())
+    }
+
+    pub fn load_rom(&mut self, rom_bytes: &[u8]) -> Result<(), &'static str> {
+        println!("Loading cartridge rom");
+        self.cartridge.load_rom(rom_bytes)
+    }
+
+    /// Whether anything answers at `address`. Reads from anywhere else see open bus.
+    pub fn is_mapped(address: usize) -> bool {
+        const BIOS_END: usize = 16384usize - 1;
+        matches!(
+            address,
+            0..=BIOS_END | 0x02000000..=0x03ffffff | 0x04000000..=0x040003ff | 0x05000000..=0x0fffffff
+        )
+    }
+
+    /// Unmapped addresses read as 0, as the opcode the CPU last prefetched (which real open
+    ///   bus would return) isn't tracked.
+    pub fn get_byte(&self, address: usize) -> u8 {
+        const BIOS_END: usize = 16384usize - 1;
+        match address {
+            0..=BIOS_END => self.bios_rom[address],
+            0x02000000..=0x02ffffff => self.ewram[address % EWRAM_SIZE],
+            0x03000000..=0x03ffffff => self.iwram[address % IWRAM_SIZE],
+            0x04000000..=0x040003ff => io::read(self, address as u32, 1) as u8,
+            0x05000000..=0x07ffffff => self.ppu.read_byte(address as u32),
+            0x08000000..=0x0dffffff => self.cartridge.read_rom_byte(address as u32),
+            0x0e000000..=0x0fffffff => self.cartridge.read_backup_byte(address as u32),
+            _ => 0,
+        }
+    }
+
+    /// Takes `&mut self` as some devices, such as the EEPROM, shift data out on every read.
+    pub fn get_halfword(&mut self, address: usize) -> u16 {
+        let address = address & (!1usize); // Mask off lowest bit to ensure alignment
+        if self.cartridge.is_eeprom_address(address as u32) {
+            return self.cartridge.read_eeprom();
+        }
+        if io::is_io_address(address as u32) {
+            return io::read(self, address as u32, 2) as u16;
+        }
+        ((self.get_byte(address + 1) as u16) << 8) | (self.get_byte(address) as u16)
+    }
+
+    pub fn get_word(&mut self, address: usize) -> u32 {
+        const BIOS_END: usize = 16384usize - 1;
+        let address = address & (!3usize); // Mask off lowest two bits to ensure alignment
+        match address {
+            0..=BIOS_END => get_word(&self.bios_rom, address),
+            0x04000000..=0x040003ff => io::read(self, address as u32, 4),
+            _ => {
+                ((self.get_halfword(address + 2) as u32) << 16)
+                    | (self.get_halfword(address) as u32)
+            }
+        }
+    }
+
+    /// Writes to the BIOS and to unmapped addresses are ignored.
+    pub fn set_byte(&mut self, address: usize, value: u8) {
+        match address {
+            0x02000000..=0x02ffffff => self.ewram[address % EWRAM_SIZE] = value,
+            0x03000000..=0x03ffffff => self.iwram[address % IWRAM_SIZE] = value,
+            0x04000000..=0x040003ff => io::write(self, address as u32, value as u32, 1),
+            0x05000000..=0x07ffffff => self.ppu.write_byte(&self.io, address as u32, value),
+            0x08000000..=0x0dffffff => {} // Only halfword writes reach the GPIO port
+            0x0e000000..=0x0fffffff => self.cartridge.write_backup_byte(address as u32, value),
+            _ => {}
+        }
+    }
+
+    pub fn set_halfword(&mut self, address: usize, value: u16) {
+        let address = address & (!1usize); // Mask off lowest bit to ensure alignment
+        if self.cartridge.is_eeprom_address(address as u32) {
+            return self.cartridge.write_eeprom(value);
+        }
+        match address {
+            0x04000000..=0x040003ff => io::write(self, address as u32, value as u32, 2),
+            0x05000000..=0x07ffffff => self.ppu.write_halfword(address as u32, value),
+            0x08000000..=0x0dffffff => self.cartridge.write_rom_halfword(address as u32, value),
+            _ => {
+                self.set_byte(address, value as u8);
+                self.set_byte(address + 1, (value >> 8) as u8);
+            }
+        }
+    }
+
+    pub fn set_word(&mut self, address: usize, value: u32) {
+        let address = address & (!3usize); // Mask off lowest two bits to ensure alignment
+        match address {
+            0x04000000..=0x040003ff => io::write(self, address as u32, value, 4),
+            _ => {
+                self.set_halfword(address, value as u16);
+                self.set_halfword(address + 2, (value >> 16) as u16);
+            }
+        }
+    }
+
+    pub fn observe_dma(&mut self, src: usize, dst: usize, num_units: usize) {
+        if self.cartridge.is_eeprom_address(src as u32)
+            || self.cartridge.is_eeprom_address(dst as u32)
+        {
+            self.cartridge.observe_eeprom_dma(num_units)
+        }
+    }
+
+    /// Runs the peripherals for the cycles the CPU just spent.
+    pub fn step(&mut self, cycles: usize) {
+        let events = self.ppu.step(&mut self.io, cycles);
+        if events.hblank {
+            self.trigger_dma(DmaTiming::HBlank);
+        }
+        if events.vblank.is_some() {
+            self.trigger_dma(DmaTiming::VBlank);
+        }
+
+        // Timers 0 and 1 pace the Direct Sound FIFOs
+        let overflows = self.timers.step(&mut self.io, cycles);
+        for (timer, count) in overflows.iter().take(2).enumerate() {
+            for _ in 0..*count {
+                let refills = self.apu.timer_overflow(&self.io, timer);
+                for (refill, fifo) in refills.into_iter().zip([FIFO_A, FIFO_B]) {
+                    if refill {
+                        self.request_fifo_dma(fifo);
+                    }
+                }
+            }
+        }
+        self.apu.step(&self.io, cycles, events.vblank);
+        self.cartridge.step(cycles);
+    }
+
+    /// Cycles the CPU spends on an access, as configured by WAITCNT.
+    fn access_cycles(
+        &mut self,
+        address: u32,
+        width: AccessWidth,
+        access: AccessType,
+        is_code: bool,
+    ) -> usize {
+        self.wait_control
+            .access_cycles(address, width, access, is_code)
+    }
+
+    pub fn advance_mem_cursor(&mut self) {
+        self.print_cursor = self.print_cursor.saturating_add(8);
+    }
+
+    pub fn regress_mem_cursor(&mut self) {
+        self.print_cursor = self.print_cursor.saturating_sub(8);
+    }
+
+    pub fn print_memory(&self, num_bytes: usize) -> String {
+        let mut ret_str: String = String::new();
+        let num_lines = num_bytes / 8;
+        ret_str.push_str("             07 06 05 04 03 02 01 00\n");
+        ret_str.push_str("-----------|-------------------------|\n");
+
+        for line_offset in 0..num_lines {
+            ret_str.push_str(
+                format!(
+                    "{:#010x} | {:02x} {:02x} {:02x} {:02x} {:02x} {:02x} {:02x} {:02x} |\n",
+                    line_offset * 8 + self.print_cursor,
+                    self.get_byte(line_offset * 8 + self.print_cursor + 7),
+                    self.get_byte(line_offset * 8 + self.print_cursor + 6),
+                    self.get_byte(line_offset * 8 + self.print_cursor + 5),
+                    self.get_byte(line_offset * 8 + self.print_cursor + 4),
+                    self.get_byte(line_offset * 8 + self.print_cursor + 3),
+                    self.get_byte(line_offset * 8 + self.print_cursor + 2),
+                    self.get_byte(line_offset * 8 + self.print_cursor + 1),
+                    self.get_byte(line_offset * 8 + self.print_cursor),
+                )
+                .as_str(),
+            );
+        }
+
+        ret_str.push_str("-----------|-------------------------|\n");
+
+        ret_str
+    }
+}
+
+/// The GBA system bus, timed by WAITCNT and the Game Pak prefetcher.
+impl Bus for Memory {
+    fn fetch_word(&mut self, address: u32, access: AccessType) -> (u32, usize) {
+        let cycles = self.access_cycles(address, AccessWidth::Word, access, true);
+        (self.get_word(address as usize), cycles)
+    }
+
+    fn read_byte(&mut self, address: u32, access: AccessType) -> (u8, usize) {
+        let cycles = self.access_cycles(address, AccessWidth::Byte, access, false);
+        (self.get_byte(address as usize), cycles)
+    }
+
+    fn read_halfword(&mut self, address: u32, access: AccessType) -> (u16, usize) {
+        let cycles = self.access_cycles(address, AccessWidth::Halfword, access, false);
+        (self.get_halfword(address as usize), cycles)
+    }
+
+    fn read_word(&mut self, address: u32, access: AccessType) -> (u32, usize) {
+        let cycles = self.access_cycles(address, AccessWidth::Word, access, false);
+        (self.get_word(address as usize), cycles)
+    }
+
+    fn write_byte(&mut self, address: u32, value: u8, access: AccessType) -> usize {
+        let cycles = self.access_cycles(address, AccessWidth::Byte, access, false);
+        self.set_byte(address as usize, value);
+        cycles
+    }
+
+    fn write_halfword(&mut self, address: u32, value: u16, access: AccessType) -> usize {
+        let cycles = self.access_cycles(address, AccessWidth::Halfword, access, false);
+        self.set_halfword(address as usize, value);
+        cycles
+    }
+
+    fn write_word(&mut self, address: u32, value: u32, access: AccessType) -> usize {
+        let cycles = self.access_cycles(address, AccessWidth::Word, access, false);
+        self.set_word(address as usize, value);
+        cycles
+    }
+
+    fn irq_line(&self) -> bool {
+        interrupt::irq_line(&self.io)
+    }
+
+    /// Idle cycles leave the cartridge bus to the prefetcher.
+    fn idle(&mut self, cycles: usize) {
+        self.wait_control.idle(cycles)
+    }
+}
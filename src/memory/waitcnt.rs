@@ -0,0 +1,292 @@
+use super::Memory;
+use crate::arm7tdmi::{AccessType, AccessWidth};
+use crate::io::{IoRegisters, WAITCNT};
+use crate::util::get_bits;
+
+/// Number of halfwords the Game Pak prefetch buffer can hold.
+const PREFETCH_CAPACITY: u32 = 8;
+
+/// First access wait states selectable for SRAM and each ROM wait state region.
+const NONSEQ_WAITS: [usize; 4] = [4, 3, 2, 8];
+/// Second access wait states for ROM wait state regions 0, 1 and 2.
+const SEQ_WAITS: [[usize; 2]; 3] = [[2, 1], [4, 1], [8, 1]];
+
+/// The Game Pak prefetcher keeps reading sequential ROM halfwords ahead of the CPU
+///   whenever the cartridge bus would otherwise sit idle.
+#[derive(Default)]
+struct Prefetch {
+    // Address of the halfword the buffer will fetch next
+    next_address: u32,
+    buffered: u32,
+    progress: usize,
+}
+
+impl Prefetch {
+    fn head(&self) -> u32 {
+        self.next_address.wrapping_sub(self.buffered * 2)
+    }
+
+    fn flush(&mut self, next_address: u32) {
+        self.next_address = next_address;
+        self.buffered = 0;
+        self.progress = 0;
+    }
+}
+
+/// The WAITCNT register (0x04000204) and the access timing it controls.
+#[derive(Default)]
+pub struct WaitControl {
+    waitcnt: u16,
+    prefetch: Prefetch,
+}
+
+impl WaitControl {
+    /// Bit 15 of WAITCNT reports the Game Pak type, which is always 0 (GBA) for our
+    ///   cartridges, so the register's masks alone cover reads.
+    pub fn register_io_hooks(io: &mut IoRegisters<Memory>) {
+        io.on_write(WAITCNT, |memory, write| {
+            memory.wait_control.write_register(write.value as u16);
+            write.value
+        });
+    }
+
+    fn write_register(&mut self, value: u16) {
+        self.waitcnt = value;
+        if !self.prefetch_enabled() {
+            self.prefetch.flush(0);
+        }
+    }
+
+    fn prefetch_enabled(&self) -> bool {
+        get_bits(self.waitcnt as u32, 14, 1) != 0
+    }
+
+    fn sram_cycles(&self) -> usize {
+        1 + NONSEQ_WAITS[get_bits(self.waitcnt as u32, 0, 2) as usize]
+    }
+
+    /// Cycles for one 16-bit access to the given ROM wait state region.
+    fn rom_cycles(&self, region: usize, access: AccessType) -> usize {
+        let waitcnt = self.waitcnt as u32;
+        let offset = 2 + 3 * region as u8;
+        match access {
+            AccessType::NonSequential => 1 + NONSEQ_WAITS[get_bits(waitcnt, offset, 2) as usize],
+            AccessType::Sequential => {
+                1 + SEQ_WAITS[region][get_bits(waitcnt, offset + 2, 1) as usize]
+            }
+        }
+    }
+
+    /// Cycles taken by a CPU access. `is_code` marks opcode fetches, the only accesses
+    ///   that can be served from the prefetch buffer.
+    pub fn access_cycles(
+        &mut self,
+        address: u32,
+        width: AccessWidth,
+        access: AccessType,
+        is_code: bool,
+    ) -> usize {
+        let cycles = match address >> 24 {
+            0x08..=0x0d => return self.rom_access_cycles(address, width, access, is_code),
+            0x0e | 0x0f => self.sram_cycles(),
+            // EWRAM is behind a 16-bit bus with 2 wait states
+            0x02 => match width {
+                AccessWidth::Word => 6,
+                _ => 3,
+            },
+            // Palette RAM and VRAM have 16-bit buses
+            0x05 | 0x06 => match width {
+                AccessWidth::Word => 2,
+                _ => 1,
+            },
+            _ => 1,
+        };
+        self.idle(cycles);
+        cycles
+    }
+
+    fn rom_access_cycles(
+        &mut self,
+        address: u32,
+        width: AccessWidth,
+        access: AccessType,
+        is_code: bool,
+    ) -> usize {
+        let region = ((address >> 25) - 4) as usize;
+        let num_halfwords = if width == AccessWidth::Word { 2 } else { 1 };
+        let address = address & !1;
+
+        if !is_code || !self.prefetch_enabled() {
+            // Data accesses take the bus away from the prefetcher, discarding its contents
+            self.prefetch.flush(address + 2 * num_halfwords);
+            return self.rom_sequence_cycles(region, address, access, num_halfwords);
+        }
+
+        if self.prefetch.head() == address {
+            if self.prefetch.buffered >= num_halfwords {
+                self.prefetch.buffered -= num_halfwords;
+                self.idle(1);
+                return 1;
+            }
+            if self.prefetch.buffered > 0 || self.prefetch.progress > 0 {
+                // The buffer runs short, so the CPU waits for the fetch under way to finish
+                //   and for any halfwords still missing after it
+                let fetch_cycles = self.rom_cycles(region, AccessType::Sequential);
+                let missing = (num_halfwords - self.prefetch.buffered) as usize;
+                let cycles = fetch_cycles - self.prefetch.progress + (missing - 1) * fetch_cycles;
+                self.prefetch.flush(address + 2 * num_halfwords);
+                return cycles;
+            }
+        }
+
+        // A miss waits for the bus like any other access, then prefetching resumes behind it
+        let cycles = self.rom_sequence_cycles(region, address, access, num_halfwords);
+        self.prefetch.flush(address + 2 * num_halfwords);
+        cycles
+    }
+
+    /// Cycles for `num_halfwords` back-to-back halfword accesses, starting at `address`.
+    fn rom_sequence_cycles(
+        &self,
+        region: usize,
+        address: u32,
+        access: AccessType,
+        num_halfwords: u32,
+    ) -> usize {
+        // The cartridge restarts the sequence at every 128KiB boundary
+        let access = if address & 0x1ffff == 0 {
+            AccessType::NonSequential
+        } else {
+            access
+        };
+        let first = self.rom_cycles(region, access);
+        let rest = (num_halfwords - 1) as usize * self.rom_cycles(region, AccessType::Sequential);
+        first + rest
+    }
+
+    /// Lets the prefetcher use cycles in which the CPU leaves the cartridge bus alone.
+    pub fn idle(&mut self, cycles: usize) {
+        let in_rom = (0x08..=0x0d).contains(&(self.prefetch.next_address >> 24));
+        if !in_rom || !self.prefetch_enabled() || self.prefetch.buffered >= PREFETCH_CAPACITY {
+            return;
+        }
+
+        let region = ((self.prefetch.next_address >> 25) - 4) as usize;
+        let fetch_cycles = self.rom_cycles(region, AccessType::Sequential);
+        self.prefetch.progress += cycles;
+        while self.prefetch.progress >= fetch_cycles && self.prefetch.buffered < PREFETCH_CAPACITY {
+            self.prefetch.progress -= fetch_cycles;
+            self.prefetch.buffered += 1;
+            self.prefetch.next_address += 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAITCNT_PREFETCH: u16 = 1 << 14;
+    const ROM: u32 = 0x0800_0000;
+
+    fn wait_control(waitcnt: u16) -> WaitControl {
+        let mut wait_control = WaitControl::default();
+        wait_control.write_register(waitcnt);
+        wait_control
+    }
+
+    fn data_read(wait_control: &mut WaitControl, address: u32, access: AccessType) -> usize {
+        wait_control.access_cycles(address, AccessWidth::Halfword, access, false)
+    }
+
+    fn code_fetch(wait_control: &mut WaitControl, address: u32) -> usize {
+        wait_control.access_cycles(address, AccessWidth::Halfword, AccessType::Sequential, true)
+    }
+
+    #[test]
+    fn rom_regions_take_their_configured_waits() {
+        let nonsequential = [5, 4, 3, 9];
+        let sequential = [[3, 2], [5, 2], [9, 2]];
+        for (region, sequential) in sequential.iter().enumerate() {
+            let address = ROM + region as u32 * 0x0200_0000 + 2;
+            let offset = 2 + 3 * region;
+            for (setting, cycles) in nonsequential.iter().enumerate() {
+                let mut wait_control = wait_control((setting as u16) << offset);
+                let access = AccessType::NonSequential;
+                assert_eq!(data_read(&mut wait_control, address, access), *cycles);
+            }
+            for (setting, cycles) in sequential.iter().enumerate() {
+                let mut wait_control = wait_control((setting as u16) << (offset + 2));
+                let access = AccessType::Sequential;
+                assert_eq!(data_read(&mut wait_control, address, access), *cycles);
+            }
+        }
+    }
+
+    #[test]
+    fn sequential_accesses_restart_at_128k_boundaries() {
+        let mut wait_control = wait_control(0);
+        assert_eq!(
+            data_read(&mut wait_control, ROM + 0x2_0000, AccessType::Sequential),
+            5
+        );
+        assert_eq!(
+            data_read(&mut wait_control, ROM + 0x2_0002, AccessType::Sequential),
+            3
+        );
+        // A word starting there pays the first access once
+        let word = wait_control.access_cycles(
+            ROM + 0x4_0000,
+            AccessWidth::Word,
+            AccessType::Sequential,
+            false,
+        );
+        assert_eq!(word, 5 + 3);
+    }
+
+    #[test]
+    fn sram_takes_its_configured_waits() {
+        for (setting, cycles) in [5, 4, 3, 9].into_iter().enumerate() {
+            let mut wait_control = wait_control(setting as u16);
+            for width in [AccessWidth::Byte, AccessWidth::Word] {
+                let access = AccessType::NonSequential;
+                let taken = wait_control.access_cycles(0x0e00_0000, width, access, false);
+                assert_eq!(taken, cycles);
+            }
+        }
+    }
+
+    #[test]
+    fn prefetched_opcodes_take_one_cycle() {
+        let mut wait_control = wait_control(WAITCNT_PREFETCH);
+        // The first fetch is a non-sequential miss
+        assert_eq!(code_fetch(&mut wait_control, ROM), 5);
+        // Two sequential fetches of 3 cycles each fit in the idle time
+        wait_control.idle(6);
+        assert_eq!(code_fetch(&mut wait_control, ROM + 2), 1);
+        assert_eq!(code_fetch(&mut wait_control, ROM + 4), 1);
+    }
+
+    #[test]
+    fn fetches_wait_only_for_the_rest_of_a_prefetch_under_way() {
+        let mut wait_control = wait_control(WAITCNT_PREFETCH);
+        code_fetch(&mut wait_control, ROM);
+        wait_control.idle(2);
+        assert_eq!(code_fetch(&mut wait_control, ROM + 2), 1);
+
+        // With one halfword buffered and another a cycle in, a word needs 2 more cycles
+        wait_control.idle(4);
+        let word =
+            wait_control.access_cycles(ROM + 4, AccessWidth::Word, AccessType::Sequential, true);
+        assert_eq!(word, 2);
+    }
+
+    #[test]
+    fn data_accesses_flush_the_prefetch_buffer() {
+        let mut wait_control = wait_control(WAITCNT_PREFETCH);
+        code_fetch(&mut wait_control, ROM);
+        wait_control.idle(6);
+        data_read(&mut wait_control, ROM + 0x100, AccessType::NonSequential);
+        assert_eq!(code_fetch(&mut wait_control, ROM + 2), 3);
+    }
+}
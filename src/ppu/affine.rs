@@ -0,0 +1,98 @@
+use super::background::BgControl;
+use super::{Ppu, SCREEN_WIDTH};
+use crate::io::{IoRegisters, BG2PA, BG2X, BG2Y, BG3X, BG3Y};
+
+/// BGxPA-PD for an affine background, as 8.8 fixed point.
+#[derive(Debug, Clone, Copy)]
+pub struct AffineParams {
+    pub pa: i32,
+    pub pb: i32,
+    pub pc: i32,
+    pub pd: i32,
+}
+
+impl AffineParams {
+    /// `bg` is 2 or 3.
+    pub fn read<C>(io: &IoRegisters<C>, bg: usize) -> Self {
+        let base = BG2PA + (bg as u32 - 2) * 0x10;
+        let param = |offset: u32| io.get(base + offset) as u16 as i16 as i32;
+        Self {
+            pa: param(0),
+            pb: param(2),
+            pc: param(4),
+            pd: param(6),
+        }
+    }
+}
+
+/// The internal copy of BGxX/BGxY the PPU actually draws from. It is reloaded from the
+///   registers at V-Blank and whenever they are written, and advanced by PB/PD after each
+///   scanline, so games can warp the picture by rewriting the parameters mid-frame.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AffineReference {
+    /// 20.8 fixed point texture coordinates of the current scanline's first dot.
+    pub x: i32,
+    pub y: i32,
+}
+
+impl AffineReference {
+    /// The registers of BG2 and BG3, in that order.
+    pub const REGISTERS: [(u32, u32); 2] = [(BG2X, BG2Y), (BG3X, BG3Y)];
+
+    pub fn latch_x(&mut self, register: u32) {
+        self.x = sign_extend_28(register);
+    }
+
+    pub fn latch_y(&mut self, register: u32) {
+        self.y = sign_extend_28(register);
+    }
+
+    pub fn latch<C>(&mut self, io: &IoRegisters<C>, bg: usize) {
+        let (x, y) = Self::REGISTERS[bg - 2];
+        self.latch_x(io.get(x));
+        self.latch_y(io.get(y));
+    }
+
+    pub fn advance(&mut self, params: &AffineParams) {
+        self.x = self.x.wrapping_add(params.pb);
+        self.y = self.y.wrapping_add(params.pd);
+    }
+}
+
+fn sign_extend_28(value: u32) -> i32 {
+    ((value << 4) as i32) >> 4
+}
+
+impl Ppu {
+    /// Draws one scanline of a rotation/scaling background. Its square map holds one byte
+    ///   per 256 color tile, 16 to 128 tiles wide.
+    pub(super) fn render_affine_line(
+        &self,
+        control: &BgControl,
+        params: &AffineParams,
+        reference: &AffineReference,
+        line: &mut [Option<u16>; SCREEN_WIDTH],
+    ) {
+        let size = 128 << control.size;
+        let tiles_per_row = (size / 8) as usize;
+
+        for (dot, pixel) in line.iter_mut().enumerate() {
+            let mut x = (reference.x + params.pa * dot as i32) >> 8;
+            let mut y = (reference.y + params.pc * dot as i32) >> 8;
+            if control.wraparound {
+                x = x.rem_euclid(size);
+                y = y.rem_euclid(size);
+            } else if !(0..size).contains(&x) || !(0..size).contains(&y) {
+                *pixel = None;
+                continue;
+            }
+
+            let (x, y) = (x as usize, y as usize);
+            let tile = self.vram[control.screen_base + (y / 8) * tiles_per_row + x / 8] as usize;
+            *pixel = match self.tile_dot(control.char_base + tile * 64, true, x % 8, y % 8) {
+                0 => None,
+                color => Some(self.palette_color(color as usize)),
+            };
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use super::Ppu;
+use crate::io::{IoRegisters, BG0CNT};
+use crate::util::get_bits;
+
+/// Character data can't reach past the 64KiB of background VRAM in the tiled modes.
+const BG_VRAM_END: usize = 0x10000;
+
+/// The fields of a BGxCNT register.
+#[derive(Debug, Clone, Copy)]
+pub struct BgControl {
+    pub priority: u32,
+    pub char_base: usize,
+    pub mosaic: bool,
+    /// 256 colors with a single palette, instead of 16 palettes of 16 colors.
+    pub color_256: bool,
+    pub screen_base: usize,
+    /// Affine backgrounds only: whether the map repeats past its edges.
+    pub wraparound: bool,
+    pub size: u32,
+}
+
+impl BgControl {
+    pub fn read<C>(io: &IoRegisters<C>, bg: usize) -> Self {
+        let bgcnt = io.get(BG0CNT + 2 * bg as u32);
+        Self {
+            priority: get_bits(bgcnt, 0, 2),
+            char_base: get_bits(bgcnt, 2, 2) as usize * 0x4000,
+            mosaic: get_bits(bgcnt, 6, 1) != 0,
+            color_256: get_bits(bgcnt, 7, 1) != 0,
+            screen_base: get_bits(bgcnt, 8, 5) as usize * 0x800,
+            wraparound: get_bits(bgcnt, 13, 1) != 0,
+            size: get_bits(bgcnt, 14, 2),
+        }
+    }
+}
+
+impl Ppu {
+    /// The color index of dot (`x`, `y`) of an 8x8 tile, 0 being transparent. 16 color
+    ///   tiles pack two dots per byte, the left one in the low nibble.
+    pub(super) fn tile_dot(&self, tile_address: usize, color_256: bool, x: usize, y: usize) -> u8 {
+        if color_256 {
+            let address = tile_address + y * 8 + x;
+            if address >= BG_VRAM_END {
+                return 0;
+            }
+            self.vram[address]
+        } else {
+            let address = tile_address + y * 4 + x / 2;
+            if address >= BG_VRAM_END {
+                return 0;
+            }
+            (self.vram[address] >> ((x & 1) * 4)) & 0xf
+        }
+    }
+}
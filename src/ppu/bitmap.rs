@@ -0,0 +1,47 @@
+use super::affine::{AffineParams, AffineReference};
+use super::{Ppu, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Frame 1 of modes 4 and 5 starts 40KiB into VRAM.
+const FRAME_1_OFFSET: usize = 0xa000;
+
+const MODE_5_WIDTH: i32 = 160;
+const MODE_5_HEIGHT: i32 = 128;
+
+impl Ppu {
+    /// Draws one scanline of BG2 in bitmap modes 3, 4 and 5. The bitmap is sampled through
+    ///   the affine parameters like any rotation background, but never wraps around.
+    pub(super) fn render_bitmap_line(
+        &self,
+        mode: u32,
+        frame_select: bool,
+        params: &AffineParams,
+        reference: &AffineReference,
+        line: &mut [Option<u16>; SCREEN_WIDTH],
+    ) {
+        let frame_offset = if frame_select { FRAME_1_OFFSET } else { 0 };
+        let (width, height) = match mode {
+            5 => (MODE_5_WIDTH, MODE_5_HEIGHT),
+            _ => (SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32),
+        };
+
+        for (dot, pixel) in line.iter_mut().enumerate() {
+            let x = (reference.x + params.pa * dot as i32) >> 8;
+            let y = (reference.y + params.pc * dot as i32) >> 8;
+            if !(0..width).contains(&x) || !(0..height).contains(&y) {
+                *pixel = None;
+                continue;
+            }
+
+            let index = (y * width + x) as usize;
+            *pixel = match mode {
+                3 => Some(self.vram_halfword(index * 2)),
+                4 => match self.vram[frame_offset + index] {
+                    // Palette entry 0 is transparent
+                    0 => None,
+                    entry => Some(self.palette_color(entry as usize)),
+                },
+                _ => Some(self.vram_halfword(frame_offset + index * 2)),
+            };
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use super::debug::{self, RenderDebug};
+use super::object::ObjLine;
+use super::{BgLine, SCREEN_WIDTH};
+use crate::io::{IoRegisters, BLDALPHA, BLDCNT, BLDY, WIN0H, WININ, WINOUT};
+use crate::util::get_bits;
+
+/// Bits of the layers in WININ, WINOUT and the two BLDCNT target masks.
+const LAYER_OBJ: u32 = 4;
+const LAYER_BACKDROP: u32 = 5;
+/// The sixth window control bit enables color special effects.
+const WINDOW_EFFECTS: u32 = 1 << 5;
+const ALL_LAYERS: u32 = 0x3f;
+/// The backdrop sits below every priority level.
+const BACKDROP_PRIORITY: u32 = 4;
+
+const DISPCNT_WIN0: u32 = 1 << 13;
+const DISPCNT_WIN1: u32 = 1 << 14;
+const DISPCNT_OBJ_WINDOW: u32 = 1 << 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Effect {
+    None,
+    AlphaBlend,
+    Brighten,
+    Darken,
+}
+
+/// A visible dot of one layer.
+#[derive(Debug, Clone, Copy)]
+struct Dot {
+    layer: u32,
+    color: u16,
+    priority: u32,
+    semi_transparent: bool,
+}
+
+/// The window and color special effect settings of the scanline being drawn.
+pub struct Compositor {
+    dispcnt: u32,
+    vcount: usize,
+    debug: RenderDebug,
+    // Horizontal extents of WIN0 and WIN1, when they cover this scanline
+    windows: [Option<(usize, usize)>; 2],
+    winin: u32,
+    winout: u32,
+    effect: Effect,
+    first_targets: u32,
+    second_targets: u32,
+    eva: u32,
+    evb: u32,
+    evy: u32,
+}
+
+impl Compositor {
+    pub fn new<C>(io: &IoRegisters<C>, dispcnt: u32, vcount: usize, debug: &RenderDebug) -> Self {
+        let window = |index: u32| -> Option<(usize, usize)> {
+            if dispcnt & (DISPCNT_WIN0 << index) == 0 {
+                return None;
+            }
+            let horizontal = io.get(WIN0H + 2 * index);
+            let vertical = io.get(WIN0H + 4 + 2 * index);
+            let inside = |value: usize, start: u32, end: u32| {
+                let (start, end) = (start as usize, end as usize);
+                // Windows whose start lies past their end wrap around the screen edge
+                if start <= end {
+                    (start..end).contains(&value)
+                } else {
+                    value >= start || value < end
+                }
+            };
+            inside(vcount, get_bits(vertical, 8, 8), get_bits(vertical, 0, 8)).then(|| {
+                let right = get_bits(horizontal, 0, 8).min(SCREEN_WIDTH as u32);
+                (get_bits(horizontal, 8, 8) as usize, right as usize)
+            })
+        };
+
+        let bldcnt = io.get(BLDCNT);
+        let bldalpha = io.get(BLDALPHA);
+        Self {
+            dispcnt,
+            vcount,
+            debug: *debug,
+            windows: [window(0), window(1)],
+            winin: io.get(WININ),
+            winout: io.get(WINOUT),
+            effect: match get_bits(bldcnt, 6, 2) {
+                0 => Effect::None,
+                1 => Effect::AlphaBlend,
+                2 => Effect::Brighten,
+                _ => Effect::Darken,
+            },
+            first_targets: get_bits(bldcnt, 0, 6),
+            second_targets: get_bits(bldcnt, 8, 6),
+            eva: get_bits(bldalpha, 0, 5).min(16),
+            evb: get_bits(bldalpha, 8, 5).min(16),
+            evy: get_bits(io.get(BLDY), 0, 5).min(16),
+        }
+    }
+
+    /// The layers and effects enabled at `dot`. WIN0 takes precedence over WIN1, which
+    ///   takes precedence over the OBJ window; dots outside all of them use WINOUT.
+    fn window_mask(&self, objects: Option<&ObjLine>, dot: usize) -> u32 {
+        let windows = DISPCNT_WIN0 | DISPCNT_WIN1 | DISPCNT_OBJ_WINDOW;
+        if self.dispcnt & windows == 0 || !self.debug.windows {
+            return ALL_LAYERS;
+        }
+
+        for (index, window) in self.windows.iter().enumerate() {
+            if let Some((left, right)) = *window {
+                let inside = if left <= right {
+                    (left..right).contains(&dot)
+                } else {
+                    dot >= left || dot < right
+                };
+                if inside {
+                    return get_bits(self.winin, 8 * index as u8, 6);
+                }
+            }
+        }
+        let in_obj_window = objects.is_some_and(|objects| objects.window[dot]);
+        if self.dispcnt & DISPCNT_OBJ_WINDOW != 0 && in_obj_window {
+            return get_bits(self.winout, 8, 6);
+        }
+        get_bits(self.winout, 0, 6)
+    }
+
+    /// Picks the two topmost visible dots, then applies the special effects to them.
+    ///   `layers` must be sorted from front to back.
+    pub fn compose(
+        &self,
+        layers: &[BgLine],
+        objects: Option<&ObjLine>,
+        backdrop: u16,
+        row: &mut [u16; SCREEN_WIDTH],
+    ) {
+        let hidden_layers = (0..self.debug.layers.len())
+            .filter(|layer| !self.debug.layers[*layer])
+            .fold(0, |mask, layer| mask | (1 << layer));
+
+        for (dot, color) in row.iter_mut().enumerate() {
+            if let Some(layer) = self.debug.isolated_layer {
+                *color = isolated_dot(layers, objects, layer, dot)
+                    .unwrap_or_else(|| debug::checkerboard(dot, self.vcount));
+                continue;
+            }
+
+            let mask = self.window_mask(objects, dot) & !hidden_layers;
+            let obj = objects
+                .and_then(|objects| objects.pixels[dot])
+                .filter(|_| mask & (1 << LAYER_OBJ) != 0);
+
+            let mut visible = [None; 2];
+            let mut count = 0;
+            let mut push = |dot: Dot| {
+                if count < 2 {
+                    visible[count] = Some(dot);
+                    count += 1;
+                }
+            };
+            let mut obj_placed = false;
+            for layer in layers {
+                if mask & (1 << layer.bg) == 0 {
+                    continue;
+                }
+                let Some(color) = layer.pixels[dot] else {
+                    continue;
+                };
+                // Objects are drawn over backgrounds of the same priority
+                if let Some(obj) = obj.filter(|obj| !obj_placed && obj.priority <= layer.priority) {
+                    obj_placed = true;
+                    push(Dot {
+                        layer: LAYER_OBJ,
+                        color: obj.color,
+                        priority: obj.priority,
+                        semi_transparent: obj.semi_transparent,
+                    });
+                }
+                push(Dot {
+                    layer: layer.bg as u32,
+                    color,
+                    priority: layer.priority,
+                    semi_transparent: false,
+                });
+            }
+            if let Some(obj) = obj.filter(|_| !obj_placed) {
+                push(Dot {
+                    layer: LAYER_OBJ,
+                    color: obj.color,
+                    priority: obj.priority,
+                    semi_transparent: obj.semi_transparent,
+                });
+            }
+            push(Dot {
+                layer: LAYER_BACKDROP,
+                color: backdrop,
+                priority: BACKDROP_PRIORITY,
+                semi_transparent: false,
+            });
+
+            let top = visible[0].unwrap();
+            *color = if mask & WINDOW_EFFECTS != 0 && self.debug.blending {
+                self.apply_effect(top, visible[1])
+            } else {
+                top.color
+            };
+            if self.debug.priority_heatmap {
+                *color = debug::heat(*color, top.priority);
+            }
+        }
+    }
+
+    fn apply_effect(&self, top: Dot, below: Option<Dot>) -> u16 {
+        let below = below.filter(|below| self.second_targets & (1 << below.layer) != 0);
+
+        // Semi-transparent objects blend with whatever second target lies below them,
+        //   regardless of the selected effect
+        if let (true, Some(below)) = (top.semi_transparent, below) {
+            return blend(top.color, below.color, self.eva, self.evb);
+        }
+        if self.first_targets & (1 << top.layer) == 0 {
+            return top.color;
+        }
+        match (self.effect, below) {
+            (Effect::AlphaBlend, Some(below)) => blend(top.color, below.color, self.eva, self.evb),
+            (Effect::Brighten, _) => map_channels(top.color, |c| c + (((31 - c) * self.evy) >> 4)),
+            (Effect::Darken, _) => map_channels(top.color, |c| c - ((c * self.evy) >> 4)),
+            _ => top.color,
+        }
+    }
+}
+
+/// The dot of a single layer, ignoring windows and effects.
+fn isolated_dot(
+    layers: &[BgLine],
+    objects: Option<&ObjLine>,
+    layer: usize,
+    dot: usize,
+) -> Option<u16> {
+    if layer == LAYER_OBJ as usize {
+        return objects
+            .and_then(|objects| objects.pixels[dot])
+            .map(|obj| obj.color);
+    }
+    layers
+        .iter()
+        .find(|line| line.bg == layer)
+        .and_then(|line| line.pixels[dot])
+}
+
+fn map_channels(color: u16, f: impl Fn(u32) -> u32) -> u16 {
+    (0..3).fold(0, |result, channel| {
+        let value = (color as u32 >> (channel * 5)) & 0x1f;
+        result | ((f(value).min(31) as u16) << (channel * 5))
+    })
+}
+
+fn blend(top: u16, bottom: u16, eva: u32, evb: u32) -> u16 {
+    (0..3).fold(0, |result, channel| {
+        let a = (top as u32 >> (channel * 5)) & 0x1f;
+        let b = (bottom as u32 >> (channel * 5)) & 0x1f;
+        result | ((((a * eva + b * evb) >> 4).min(31) as u16) << (channel * 5))
+    })
+}
@@ -0,0 +1,50 @@
+/// Names of the layers `RenderDebug` switches, in the order of its `layers`.
+pub const LAYER_NAMES: [&str; 5] = ["BG0", "BG1", "BG2", "BG3", "OBJ"];
+
+/// Heatmap tints for the priority of the topmost layer, from 0 (red) to 3 (blue).
+///   The backdrop isn't tinted.
+pub const PRIORITY_COLORS: [u16; 4] = [0x001f, 0x021f, 0x03e0, 0x7c00];
+
+const CHECKERBOARD_COLORS: [u16; 2] = [0x318c, 0x4a52];
+const CHECKERBOARD_SIZE: usize = 4;
+
+/// Rendering switches for tracking down drawing bugs. They only change the picture, never
+///   what the emulated hardware sees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderDebug {
+    /// BG0-BG3, then OBJ.
+    pub layers: [bool; 5],
+    pub windows: bool,
+    pub blending: bool,
+    /// Draws one layer by itself, over a checkerboard showing through its transparent dots.
+    pub isolated_layer: Option<usize>,
+    pub priority_heatmap: bool,
+}
+
+impl Default for RenderDebug {
+    fn default() -> Self {
+        Self {
+            layers: [true; 5],
+            windows: true,
+            blending: true,
+            isolated_layer: None,
+            priority_heatmap: false,
+        }
+    }
+}
+
+pub fn checkerboard(x: usize, y: usize) -> u16 {
+    CHECKERBOARD_COLORS[(x / CHECKERBOARD_SIZE + y / CHECKERBOARD_SIZE) % 2]
+}
+
+/// Mixes `color` halfway towards the tint of `priority`.
+pub fn heat(color: u16, priority: u32) -> u16 {
+    let Some(tint) = PRIORITY_COLORS.get(priority as usize) else {
+        return color;
+    };
+    (0..3).fold(0, |result, channel| {
+        let a = (color >> (channel * 5)) & 0x1f;
+        let b = (tint >> (channel * 5)) & 0x1f;
+        result | (((a + b) / 2) << (channel * 5))
+    })
+}
@@ -0,0 +1,435 @@
+mod affine;
+mod background;
+mod bitmap;
+mod compose;
+mod debug;
+mod mosaic;
+mod object;
+mod text;
+
+use crate::interrupt::{self, Interrupt};
+use crate::io::{IoContext, IoRegisters, BG2X, BG2Y, BG3X, BG3Y, DISPCNT, DISPSTAT, VCOUNT};
+use crate::memory::Memory;
+use crate::util::get_bits;
+use affine::{AffineParams, AffineReference};
+use background::BgControl;
+use compose::Compositor;
+pub use debug::{RenderDebug, LAYER_NAMES, PRIORITY_COLORS};
+use mosaic::{MosaicCounter, MosaicSize};
+
+pub const SCREEN_WIDTH: usize = 240;
+pub const SCREEN_HEIGHT: usize = 160;
+
+const PALETTE_SIZE: usize = 0x400;
+const VRAM_SIZE: usize = 0x18000;
+const OAM_SIZE: usize = 0x400;
+/// In the bitmap modes, object tiles start past the 80KiB frame buffer area.
+const BITMAP_OBJ_VRAM_START: usize = 0x14000;
+const TILED_OBJ_VRAM_START: usize = 0x10000;
+
+/// Forced blank shows white while giving the CPU free access to video memory.
+const FORCED_BLANK_COLOR: u16 = 0x7fff;
+
+/// Each dot takes 4 CPU cycles: 240 visible dots and 68 dots of horizontal blanking.
+const CYCLES_PER_LINE: usize = 308 * 4;
+/// The H-Blank flag rises a little after the last visible dot is drawn.
+const HDRAW_CYCLES: usize = 1006;
+const LINES_PER_FRAME: u16 = 228;
+
+const DISPSTAT_VBLANK: u32 = 1 << 0;
+const DISPSTAT_HBLANK: u32 = 1 << 1;
+const DISPSTAT_VCOUNT: u32 = 1 << 2;
+const DISPSTAT_VBLANK_IRQ: u32 = 1 << 3;
+const DISPSTAT_HBLANK_IRQ: u32 = 1 << 4;
+const DISPSTAT_VCOUNT_IRQ: u32 = 1 << 5;
+
+const DISPCNT_FRAME_SELECT: u32 = 1 << 4;
+const DISPCNT_FORCED_BLANK: u32 = 1 << 7;
+const DISPCNT_OBJ: u32 = 1 << 12;
+
+/// What happened during a call to `Ppu::step`, for the DMA channels that wait on it.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PpuEvents {
+    /// H-Blank began on a visible line.
+    pub hblank: bool,
+    /// V-Blank began, this many cycles into the step.
+    pub vblank: Option<usize>,
+}
+
+/// One background's contribution to the scanline being drawn.
+struct BgLine {
+    bg: usize,
+    priority: u32,
+    pixels: [Option<u16>; SCREEN_WIDTH],
+}
+
+/// The display controller. It walks the scanlines in step with the CPU, keeping DISPSTAT
+///   and VCOUNT current, raising the display interrupts and drawing each visible line
+///   as it is reached.
+pub struct Ppu {
+    // Cycles into the current scanline
+    line_cycles: usize,
+    in_hblank: bool,
+    frame_count: u64,
+    palette: Vec<u8>,
+    vram: Vec<u8>,
+    oam: Vec<u8>,
+    // Internal reference points of BG2 and BG3
+    affine: [AffineReference; 2],
+    bg_mosaic: MosaicCounter,
+    obj_mosaic: MosaicCounter,
+    // The frame being drawn, the last one completed and the one before it, as BGR555 colors
+    back_buffer: Vec<u16>,
+    front_buffer: Vec<u16>,
+    previous_buffer: Vec<u16>,
+    pub debug: RenderDebug,
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self {
+            line_cycles: 0,
+            in_hblank: false,
+            frame_count: 0,
+            palette: vec![0u8; PALETTE_SIZE],
+            vram: vec![0u8; VRAM_SIZE],
+            oam: vec![0u8; OAM_SIZE],
+            affine: [AffineReference::default(); 2],
+            bg_mosaic: MosaicCounter::default(),
+            obj_mosaic: MosaicCounter::default(),
+            back_buffer: vec![0u16; SCREEN_WIDTH * SCREEN_HEIGHT],
+            front_buffer: vec![0u16; SCREEN_WIDTH * SCREEN_HEIGHT],
+            previous_buffer: vec![0u16; SCREEN_WIDTH * SCREEN_HEIGHT],
+            debug: RenderDebug::default(),
+        }
+    }
+}
+
+impl Ppu {
+    pub fn register_io_hooks(io: &mut IoRegisters<Memory>) {
+        // Changing the VCount setting updates the match flag at once, without an interrupt
+        io.on_write(DISPSTAT, |context, write| {
+            let vcount = context.io().get(VCOUNT);
+            if vcount == get_bits(write.value, 8, 8) {
+                write.value | DISPSTAT_VCOUNT
+            } else {
+                write.value & !DISPSTAT_VCOUNT
+            }
+        });
+
+        // Writing a reference point reloads the internal copy, even in the middle of a frame
+        io.on_write(BG2X, |memory, write| {
+            memory.ppu.affine[0].latch_x(write.value);
+            write.value
+        });
+        io.on_write(BG2Y, |memory, write| {
+            memory.ppu.affine[0].latch_y(write.value);
+            write.value
+        });
+        io.on_write(BG3X, |memory, write| {
+            memory.ppu.affine[1].latch_x(write.value);
+            write.value
+        });
+        io.on_write(BG3Y, |memory, write| {
+            memory.ppu.affine[1].latch_y(write.value);
+            write.value
+        });
+    }
+
+    /// The last completed frame, as 240x160 BGR555 colors.
+    pub fn frame(&self) -> &[u16] {
+        &self.front_buffer
+    }
+
+    /// The frame completed before `frame`, for blending the two.
+    pub fn previous_frame(&self) -> &[u16] {
+        &self.previous_buffer
+    }
+
+    /// Frames completed since power on, counted at the start of V-Blank.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn step<C>(&mut self, io: &mut IoRegisters<C>, cycles: usize) -> PpuEvents {
+        let mut events = PpuEvents::default();
+        self.line_cycles += cycles;
+        loop {
+            if !self.in_hblank && self.line_cycles >= HDRAW_CYCLES {
+                self.enter_hblank(io);
+                events.hblank |= (io.get(VCOUNT) as usize) < SCREEN_HEIGHT;
+            } else if self.line_cycles >= CYCLES_PER_LINE {
+                self.line_cycles -= CYCLES_PER_LINE;
+                self.next_line(io);
+                if io.get(VCOUNT) as usize == SCREEN_HEIGHT {
+                    events.vblank = Some(cycles.saturating_sub(self.line_cycles));
+                }
+            } else {
+                break;
+            }
+        }
+        events
+    }
+
+    /// H-Blank happens on every line, including the ones inside V-Blank.
+    fn enter_hblank<C>(&mut self, io: &mut IoRegisters<C>) {
+        self.in_hblank = true;
+        let vcount = io.get(VCOUNT) as usize;
+        if vcount < SCREEN_HEIGHT {
+            self.render_scanline(io, vcount);
+        }
+
+        let dispstat = io.get(DISPSTAT) | DISPSTAT_HBLANK;
+        io.set(DISPSTAT, dispstat);
+        if dispstat & DISPSTAT_HBLANK_IRQ != 0 {
+            interrupt::request(io, Interrupt::HBlank);
+        }
+    }
+
+    fn next_line<C>(&mut self, io: &mut IoRegisters<C>) {
+        self.in_hblank = false;
+        let vcount = (io.get(VCOUNT) as u16 + 1) % LINES_PER_FRAME;
+        io.set(VCOUNT, vcount as u32);
+
+        let mut dispstat = io.get(DISPSTAT) & !DISPSTAT_HBLANK;
+        if vcount == SCREEN_HEIGHT as u16 {
+            dispstat |= DISPSTAT_VBLANK;
+            self.frame_count += 1;
+            // The oldest frame's buffer is drawn over next
+            std::mem::swap(&mut self.previous_buffer, &mut self.front_buffer);
+            std::mem::swap(&mut self.back_buffer, &mut self.front_buffer);
+            for (bg, reference) in self.affine.iter_mut().enumerate() {
+                reference.latch(io, bg + 2);
+            }
+            if dispstat & DISPSTAT_VBLANK_IRQ != 0 {
+                interrupt::request(io, Interrupt::VBlank);
+            }
+        } else if vcount == 0 {
+            self.bg_mosaic.reset();
+            self.obj_mosaic.reset();
+        } else if vcount == LINES_PER_FRAME - 1 {
+            // The V-Blank flag drops during the last line, ahead of the next frame
+            dispstat &= !DISPSTAT_VBLANK;
+        }
+        io.set(DISPSTAT, dispstat);
+        Self::update_vcount_match(io);
+    }
+
+    /// Compares VCOUNT against the VCount setting in the upper byte of DISPSTAT.
+    fn update_vcount_match<C>(io: &mut IoRegisters<C>) {
+        let dispstat = io.get(DISPSTAT);
+        let matched = io.get(VCOUNT) == get_bits(dispstat, 8, 8);
+        if matched {
+            io.set(DISPSTAT, dispstat | DISPSTAT_VCOUNT);
+            if dispstat & DISPSTAT_VCOUNT_IRQ != 0 {
+                interrupt::request(io, Interrupt::VCount);
+            }
+        } else {
+            io.set(DISPSTAT, dispstat & !DISPSTAT_VCOUNT);
+        }
+    }
+
+    fn render_scanline<C>(&mut self, io: &IoRegisters<C>, vcount: usize) {
+        let dispcnt = io.get(DISPCNT);
+        let mosaic = MosaicSize::read(io);
+        let mut row = [FORCED_BLANK_COLOR; SCREEN_WIDTH];
+
+        if dispcnt & DISPCNT_FORCED_BLANK == 0 {
+            let mut layers = self.render_backgrounds(io, dispcnt, &mosaic, vcount);
+            // Lower priority values are drawn on top, and BG0 wins ties over BG1 and so on
+            layers.sort_by_key(|layer| (layer.priority, layer.bg));
+            let objects = (dispcnt & DISPCNT_OBJ != 0)
+                .then(|| self.render_objects(dispcnt, &mosaic, self.obj_mosaic.line, vcount));
+
+            let compositor = Compositor::new(io, dispcnt, vcount, &self.debug);
+            compositor.compose(&layers, objects.as_ref(), self.palette_color(0), &mut row);
+        }
+        self.back_buffer[vcount * SCREEN_WIDTH..][..SCREEN_WIDTH].copy_from_slice(&row);
+
+        // The reference points move down one line, whatever the mode
+        for (bg, reference) in self.affine.iter_mut().enumerate() {
+            reference.advance(&AffineParams::read(io, bg + 2));
+        }
+        self.bg_mosaic.advance(mosaic.bg_v, vcount + 1);
+        self.obj_mosaic.advance(mosaic.obj_v, vcount + 1);
+    }
+
+    /// Draws the backgrounds enabled in DISPCNT that exist in the current mode. Mosaic
+    ///   backgrounds are drawn as they were on the line the mosaic counter last latched,
+    ///   then stretched horizontally.
+    fn render_backgrounds<C>(
+        &self,
+        io: &IoRegisters<C>,
+        dispcnt: u32,
+        mosaic: &MosaicSize,
+        vcount: usize,
+    ) -> Vec<BgLine> {
+        let mode = get_bits(dispcnt, 0, 3);
+        let mut layers = Vec::with_capacity(4);
+        for bg in 0..4 {
+            if get_bits(dispcnt, 8 + bg as u8, 1) == 0 {
+                continue;
+            }
+
+            let control = BgControl::read(io, bg);
+            let line = if control.mosaic {
+                self.bg_mosaic.line
+            } else {
+                vcount
+            };
+            // Affine layers step their reference point back to the sampled line
+            let affine = |bg: usize| {
+                let params = AffineParams::read(io, bg);
+                let lines_back = (vcount - line) as i32;
+                let reference = AffineReference {
+                    x: self.affine[bg - 2].x - params.pb * lines_back,
+                    y: self.affine[bg - 2].y - params.pd * lines_back,
+                };
+                (params, reference)
+            };
+
+            let mut pixels = [None; SCREEN_WIDTH];
+            match (mode, bg) {
+                (0, _) | (1, 0 | 1) => self.render_text_line(io, bg, &control, line, &mut pixels),
+                (1, 2) | (2, 2 | 3) => {
+                    let (params, reference) = affine(bg);
+                    self.render_affine_line(&control, &params, &reference, &mut pixels);
+                }
+                (3..=5, 2) => {
+                    let (params, reference) = affine(bg);
+                    let frame_select = dispcnt & DISPCNT_FRAME_SELECT != 0;
+                    self.render_bitmap_line(mode, frame_select, &params, &reference, &mut pixels);
+                }
+                _ => continue,
+            }
+            if control.mosaic {
+                mosaic::apply_horizontal(&mut pixels, mosaic.bg_h);
+            }
+            layers.push(BgLine {
+                bg,
+                priority: control.priority,
+                pixels,
+            });
+        }
+        layers
+    }
+
+    fn palette_color(&self, index: usize) -> u16 {
+        u16::from_le_bytes([self.palette[index * 2], self.palette[index * 2 + 1]]) & 0x7fff
+    }
+
+    fn vram_halfword(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.vram[offset], self.vram[offset + 1]]) & 0x7fff
+    }
+
+    /// VRAM repeats every 128KiB, with its last 32KiB mirroring the 32KiB before it.
+    fn vram_offset(address: u32) -> usize {
+        let offset = address as usize & 0x1ffff;
+        if offset >= VRAM_SIZE {
+            offset - 0x8000
+        } else {
+            offset
+        }
+    }
+
+    /// Reads palette RAM, VRAM or OAM.
+    pub fn read_byte(&self, address: u32) -> u8 {
+        match address >> 24 {
+            0x05 => self.palette[address as usize & (PALETTE_SIZE - 1)],
+            0x06 => self.vram[Self::vram_offset(address)],
+            _ => self.oam[address as usize & (OAM_SIZE - 1)],
+        }
+    }
+
+    pub fn write_halfword(&mut self, address: u32, value: u16) {
+        let (memory, offset) = match address >> 24 {
+            0x05 => (&mut self.palette, address as usize & (PALETTE_SIZE - 2)),
+            0x06 => (&mut self.vram, Self::vram_offset(address) & !1),
+            _ => (&mut self.oam, address as usize & (OAM_SIZE - 2)),
+        };
+        memory[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Video memory sits on a 16-bit bus: byte writes to palette RAM and background VRAM
+    ///   store the byte to both halves of the halfword, while OAM and object VRAM ignore them.
+    pub fn write_byte<C>(&mut self, io: &IoRegisters<C>, address: u32, value: u8) {
+        let halfword = u16::from_le_bytes([value, value]);
+        match address >> 24 {
+            0x05 => self.write_halfword(address, halfword),
+            0x06 => {
+                let bitmap_mode = matches!(get_bits(io.get(DISPCNT), 0, 3), 3..=5);
+                let obj_start = if bitmap_mode {
+                    BITMAP_OBJ_VRAM_START
+                } else {
+                    TILED_OBJ_VRAM_START
+                };
+                if Self::vram_offset(address) < obj_start {
+                    self.write_halfword(address, halfword);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::IF;
+
+    fn step_lines(ppu: &mut Ppu, io: &mut IoRegisters<Memory>, lines: usize) -> PpuEvents {
+        ppu.step(io, lines * CYCLES_PER_LINE)
+    }
+
+    #[test]
+    fn dispstat_follows_the_line_and_frame() {
+        let mut io = IoRegisters::<Memory>::default();
+        let mut ppu = Ppu::default();
+        let irqs = DISPSTAT_VBLANK_IRQ | DISPSTAT_HBLANK_IRQ | DISPSTAT_VCOUNT_IRQ;
+        io.set(DISPSTAT, irqs | (2 << 8));
+        let dispstat = |io: &IoRegisters<Memory>| io.get(DISPSTAT) & 0b111;
+
+        // H-Blank starts 1006 cycles into the line, and ends with it
+        let events = ppu.step(&mut io, HDRAW_CYCLES - 1);
+        assert!(!events.hblank);
+        assert_eq!(dispstat(&io), 0);
+        let events = ppu.step(&mut io, 1);
+        assert!(events.hblank);
+        assert_eq!(dispstat(&io), DISPSTAT_HBLANK);
+        assert_eq!(io.get(IF), 1 << Interrupt::HBlank as u32);
+        ppu.step(&mut io, CYCLES_PER_LINE - HDRAW_CYCLES);
+        assert_eq!(io.get(VCOUNT), 1);
+        assert_eq!(dispstat(&io), 0);
+
+        // The VCount flag is up for the matching line only
+        io.set(IF, 0);
+        step_lines(&mut ppu, &mut io, 1);
+        assert_eq!(io.get(VCOUNT), 2);
+        assert_eq!(dispstat(&io), DISPSTAT_VCOUNT);
+        assert_ne!(io.get(IF) & (1 << Interrupt::VCount as u32), 0);
+        step_lines(&mut ppu, &mut io, 1);
+        assert_eq!(dispstat(&io), 0);
+
+        // V-Blank starts as line 160 begins, and the flag drops for the last line
+        io.set(IF, 0);
+        let events = step_lines(&mut ppu, &mut io, SCREEN_HEIGHT - 4);
+        assert_eq!(events.vblank, None);
+        let events = step_lines(&mut ppu, &mut io, 1);
+        assert_eq!(events.vblank, Some(CYCLES_PER_LINE));
+        assert!(events.hblank);
+        assert_eq!(io.get(VCOUNT), SCREEN_HEIGHT as u32);
+        assert_eq!(dispstat(&io), DISPSTAT_VBLANK);
+        assert_ne!(io.get(IF) & (1 << Interrupt::VBlank as u32), 0);
+        assert_eq!(ppu.frame_count, 1);
+
+        step_lines(
+            &mut ppu,
+            &mut io,
+            LINES_PER_FRAME as usize - 1 - SCREEN_HEIGHT,
+        );
+        assert_eq!(io.get(VCOUNT), LINES_PER_FRAME as u32 - 1);
+        assert_eq!(dispstat(&io), 0);
+        step_lines(&mut ppu, &mut io, 1);
+        assert_eq!(io.get(VCOUNT), 0);
+    }
+}
@@ -0,0 +1,60 @@
+use crate::io::{IoRegisters, MOSAIC};
+use crate::util::get_bits;
+
+/// The block sizes set in the MOSAIC register, in dots.
+#[derive(Debug, Clone, Copy)]
+pub struct MosaicSize {
+    pub bg_h: usize,
+    pub bg_v: usize,
+    pub obj_h: usize,
+    pub obj_v: usize,
+}
+
+impl MosaicSize {
+    pub fn read<C>(io: &IoRegisters<C>) -> Self {
+        let mosaic = io.get(MOSAIC);
+        Self {
+            bg_h: get_bits(mosaic, 0, 4) as usize + 1,
+            bg_v: get_bits(mosaic, 4, 4) as usize + 1,
+            obj_h: get_bits(mosaic, 8, 4) as usize + 1,
+            obj_v: get_bits(mosaic, 12, 4) as usize + 1,
+        }
+    }
+}
+
+/// Tracks which scanline mosaic layers repeat. The hardware counts lines with a 4-bit
+///   counter that only restarts when it equals the block height, so shrinking the height
+///   mid-frame lets the counter run on until it wraps around.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MosaicCounter {
+    counter: usize,
+    /// The scanline sampled by mosaic layers on the current line.
+    pub line: usize,
+}
+
+impl MosaicCounter {
+    pub fn reset(&mut self) {
+        self.counter = 0;
+        self.line = 0;
+    }
+
+    /// Moves on to `next_line`, given the block height in lines.
+    pub fn advance(&mut self, height: usize, next_line: usize) {
+        if self.counter == height - 1 {
+            self.counter = 0;
+            self.line = next_line;
+        } else {
+            self.counter = (self.counter + 1) & 0xf;
+        }
+    }
+}
+
+/// Repeats the first dot of each `width` dot block across the block.
+pub fn apply_horizontal<T: Copy>(pixels: &mut [T], width: usize) {
+    if width == 1 {
+        return;
+    }
+    for dot in 0..pixels.len() {
+        pixels[dot] = pixels[dot - dot % width];
+    }
+}
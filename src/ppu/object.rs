@@ -0,0 +1,420 @@
+use super::mosaic::MosaicSize;
+use super::{Ppu, SCREEN_WIDTH};
+use crate::util::get_bits;
+
+const NUM_OBJECTS: usize = 128;
+/// Object tiles live in the last 32KiB of VRAM, and their colors in the second half of
+///   palette RAM.
+const OBJ_TILE_BASE: usize = 0x10000;
+const OBJ_PALETTE_BASE: usize = 256;
+/// In the bitmap modes the frame buffers overlap the first half of the object tiles.
+const BITMAP_MODE_FIRST_TILE: usize = 512;
+
+/// Cycles available to draw objects on each scanline. DISPCNT's H-Blank interval free bit
+///   keeps object processing out of H-Blank, leaving less time.
+const OBJ_CYCLES_PER_LINE: usize = 1210;
+const OBJ_CYCLES_PER_LINE_HBLANK_FREE: usize = 954;
+
+const DISPCNT_HBLANK_FREE: u32 = 1 << 5;
+const DISPCNT_OBJ_1D: u32 = 1 << 6;
+
+/// Width and height for each shape (square, horizontal, vertical) and size.
+const OBJ_SIZES: [[(i32, i32); 4]; 3] = [
+    [(8, 8), (16, 16), (32, 32), (64, 64)],
+    [(16, 8), (32, 8), (32, 16), (64, 32)],
+    [(8, 16), (8, 32), (16, 32), (32, 64)],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ObjMode {
+    Normal,
+    SemiTransparent,
+    Window,
+}
+
+/// An object dot that made it to the scanline.
+#[derive(Debug, Clone, Copy)]
+pub struct ObjPixel {
+    pub color: u16,
+    pub priority: u32,
+    pub semi_transparent: bool,
+}
+
+/// The objects' contribution to the scanline being drawn.
+pub struct ObjLine {
+    pub pixels: [Option<ObjPixel>; SCREEN_WIDTH],
+    /// Dots covered by the opaque parts of OBJ window objects.
+    pub window: [bool; SCREEN_WIDTH],
+}
+
+/// The attributes of one OAM entry.
+struct Obj {
+    y: i32,
+    x: i32,
+    affine: bool,
+    double_size: bool,
+    mode: ObjMode,
+    mosaic: bool,
+    color_256: bool,
+    width: i32,
+    height: i32,
+    affine_group: usize,
+    h_flip: bool,
+    v_flip: bool,
+    tile: usize,
+    priority: u32,
+    palette_bank: usize,
+}
+
+impl Obj {
+    /// Returns `None` for hidden objects and the prohibited shape and mode settings.
+    fn parse(attr0: u32, attr1: u32, attr2: u32) -> Option<Self> {
+        let affine = get_bits(attr0, 8, 1) != 0;
+        let double_size = get_bits(attr0, 9, 1) != 0;
+        // Without the affine flag, the double size bit hides the object
+        if !affine && double_size {
+            return None;
+        }
+        let mode = match get_bits(attr0, 10, 2) {
+            0 => ObjMode::Normal,
+            1 => ObjMode::SemiTransparent,
+            2 => ObjMode::Window,
+            _ => return None,
+        };
+        let shape = get_bits(attr0, 14, 2) as usize;
+        let (width, height) = *OBJ_SIZES.get(shape)?.get(get_bits(attr1, 14, 2) as usize)?;
+
+        // The X coordinate is 9-bit signed, while Y wraps around at 256
+        let x = get_bits(attr1, 0, 9) as i32;
+        Some(Self {
+            y: get_bits(attr0, 0, 8) as i32,
+            x: if x >= 256 { x - 512 } else { x },
+            affine,
+            double_size,
+            mode,
+            mosaic: get_bits(attr0, 12, 1) != 0,
+            color_256: get_bits(attr0, 13, 1) != 0,
+            width,
+            height,
+            affine_group: get_bits(attr1, 9, 5) as usize,
+            h_flip: !affine && get_bits(attr1, 12, 1) != 0,
+            v_flip: !affine && get_bits(attr1, 13, 1) != 0,
+            tile: get_bits(attr2, 0, 10) as usize,
+            priority: get_bits(attr2, 10, 2),
+            palette_bank: get_bits(attr2, 12, 4) as usize,
+        })
+    }
+
+    /// The area the object covers on screen; double size affine objects get twice the room
+    ///   to rotate into.
+    fn bounds(&self) -> (i32, i32) {
+        if self.double_size {
+            (self.width * 2, self.height * 2)
+        } else {
+            (self.width, self.height)
+        }
+    }
+}
+
+impl Ppu {
+    fn oam_halfword(&self, offset: usize) -> u32 {
+        u16::from_le_bytes([self.oam[offset], self.oam[offset + 1]]) as u32
+    }
+
+    /// Draws the objects crossing scanline `vcount`, in OAM order. The lowest numbered object
+    ///   wins among those of equal priority, and drawing stops once the scanline's cycle
+    ///   budget is spent. Mosaic objects repeat `mosaic_line` where it falls inside them.
+    pub(super) fn render_objects(
+        &self,
+        dispcnt: u32,
+        mosaic: &MosaicSize,
+        mosaic_line: usize,
+        vcount: usize,
+    ) -> ObjLine {
+        let mut line = ObjLine {
+            pixels: [None; SCREEN_WIDTH],
+            window: [false; SCREEN_WIDTH],
+        };
+        let mut budget = if dispcnt & DISPCNT_HBLANK_FREE != 0 {
+            OBJ_CYCLES_PER_LINE_HBLANK_FREE
+        } else {
+            OBJ_CYCLES_PER_LINE
+        };
+        let bitmap_mode = matches!(get_bits(dispcnt, 0, 3), 3..=5);
+        let mapping_1d = dispcnt & DISPCNT_OBJ_1D != 0;
+
+        for index in 0..NUM_OBJECTS {
+            let Some(obj) = Obj::parse(
+                self.oam_halfword(index * 8),
+                self.oam_halfword(index * 8 + 2),
+                self.oam_halfword(index * 8 + 4),
+            ) else {
+                continue;
+            };
+
+            let (bounds_width, bounds_height) = obj.bounds();
+            let row = (vcount as i32 - obj.y).rem_euclid(256);
+            if row >= bounds_height {
+                continue;
+            }
+            if bitmap_mode && obj.tile < BITMAP_MODE_FIRST_TILE {
+                continue;
+            }
+
+            // Regular objects take a cycle per dot, affine ones two plus some setup
+            let (setup_cycles, dot_cycles) = if obj.affine { (10, 2) } else { (0, 1) };
+            if budget <= setup_cycles {
+                break;
+            }
+            budget -= setup_cycles;
+            let drawn_width = (bounds_width as usize).min(budget / dot_cycles);
+            budget -= drawn_width * dot_cycles;
+
+            let row = if obj.mosaic {
+                row - row.min((vcount - mosaic_line) as i32)
+            } else {
+                row
+            };
+            let mosaic_width = if obj.mosaic { mosaic.obj_h as i32 } else { 1 };
+            self.render_object(
+                &obj,
+                row,
+                drawn_width as i32,
+                mosaic_width,
+                mapping_1d,
+                &mut line,
+            );
+            if budget == 0 {
+                break;
+            }
+        }
+        line
+    }
+
+    fn render_object(
+        &self,
+        obj: &Obj,
+        row: i32,
+        drawn_width: i32,
+        mosaic_width: i32,
+        mapping_1d: bool,
+        line: &mut ObjLine,
+    ) {
+        let (bounds_width, bounds_height) = obj.bounds();
+        let params = if obj.affine {
+            let group = obj.affine_group * 32;
+            let param = |offset: usize| self.oam_halfword(group + offset) as u16 as i16 as i32;
+            [param(6), param(14), param(22), param(30)]
+        } else {
+            [0x100, 0, 0, 0x100]
+        };
+
+        for column in 0..drawn_width {
+            let dot = obj.x + column;
+            if !(0..SCREEN_WIDTH as i32).contains(&dot) {
+                continue;
+            }
+
+            // Horizontal mosaic blocks line up with the screen, not the object
+            let column = (dot - dot % mosaic_width - obj.x).max(0);
+
+            // Rotate around the center of the bounding box
+            let dx = column - bounds_width / 2;
+            let dy = row - bounds_height / 2;
+            let mut tex_x = ((params[0] * dx + params[1] * dy) >> 8) + obj.width / 2;
+            let mut tex_y = ((params[2] * dx + params[3] * dy) >> 8) + obj.height / 2;
+            if !(0..obj.width).contains(&tex_x) || !(0..obj.height).contains(&tex_y) {
+                continue;
+            }
+            if obj.h_flip {
+                tex_x = obj.width - 1 - tex_x;
+            }
+            if obj.v_flip {
+                tex_y = obj.height - 1 - tex_y;
+            }
+
+            let color = self.obj_dot(obj, tex_x as usize, tex_y as usize, mapping_1d);
+            if color == 0 {
+                continue;
+            }
+
+            let dot = dot as usize;
+            if obj.mode == ObjMode::Window {
+                line.window[dot] = true;
+                continue;
+            }
+            if line.pixels[dot].is_some_and(|pixel| pixel.priority <= obj.priority) {
+                continue;
+            }
+            let palette_index = if obj.color_256 {
+                color as usize
+            } else {
+                obj.palette_bank * 16 + color as usize
+            };
+            line.pixels[dot] = Some(ObjPixel {
+                color: self.palette_color(OBJ_PALETTE_BASE + palette_index),
+                priority: obj.priority,
+                semi_transparent: obj.mode == ObjMode::SemiTransparent,
+            });
+        }
+    }
+
+    /// The color index of a dot of the object's image. Tiles are numbered in 32 byte units,
+    ///   so 256 color tiles take up two numbers each. With 2D mapping the tiles form a
+    ///   32x32 grid; with 1D mapping each object's tiles follow one another.
+    fn obj_dot(&self, obj: &Obj, x: usize, y: usize, mapping_1d: bool) -> u8 {
+        let units_per_tile = if obj.color_256 { 2 } else { 1 };
+        let row_units = if mapping_1d {
+            (obj.width as usize / 8) * units_per_tile
+        } else {
+            32
+        };
+        let first_tile = if obj.color_256 && !mapping_1d {
+            obj.tile & !1
+        } else {
+            obj.tile
+        };
+        let tile = (first_tile + (y / 8) * row_units + (x / 8) * units_per_tile) & 0x3ff;
+
+        // 256 color tiles past the end of object VRAM wrap around to its start
+        let address = |offset: usize| OBJ_TILE_BASE + ((tile * 32 + offset) & 0x7fff);
+        if obj.color_256 {
+            self.vram[address((y % 8) * 8 + x % 8)]
+        } else {
+            (self.vram[address((y % 8) * 4 + (x % 8) / 2)] >> ((x & 1) * 4)) & 0xf
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_MOSAIC: MosaicSize = MosaicSize {
+        bg_h: 1,
+        bg_v: 1,
+        obj_h: 1,
+        obj_v: 1,
+    };
+    const ATTR0_AFFINE: u32 = 1 << 8;
+    const ATTR0_256_COLORS: u32 = 1 << 13;
+    /// Square objects of 16x16 and 64x64 dots.
+    const ATTR1_SIZE_16: u32 = 1 << 14;
+    const ATTR1_SIZE_64: u32 = 3 << 14;
+
+    fn set_obj(ppu: &mut Ppu, index: usize, attrs: [u32; 3]) {
+        for (offset, attr) in attrs.iter().enumerate() {
+            let address = index * 8 + offset * 2;
+            ppu.oam[address..address + 2].copy_from_slice(&(*attr as u16).to_le_bytes());
+        }
+    }
+
+    fn set_obj_color(ppu: &mut Ppu, index: usize, color: u16) {
+        let address = (OBJ_PALETTE_BASE + index) * 2;
+        ppu.palette[address..address + 2].copy_from_slice(&color.to_le_bytes());
+    }
+
+    /// A PPU whose object tiles are all color 1 in 16 colors, with the affine parameters of
+    ///   group 0 left at identity. Every other object is hidden.
+    fn ppu() -> Ppu {
+        let mut ppu = Ppu::default();
+        ppu.vram[OBJ_TILE_BASE..].fill(0x11);
+        for index in 0..NUM_OBJECTS {
+            set_obj(&mut ppu, index, [0x0200, 0, 0]);
+        }
+        ppu.oam[6..8].copy_from_slice(&0x100u16.to_le_bytes());
+        ppu.oam[30..32].copy_from_slice(&0x100u16.to_le_bytes());
+        ppu
+    }
+
+    fn drawn(line: &ObjLine) -> Vec<usize> {
+        (0..SCREEN_WIDTH)
+            .filter(|dot| line.pixels[*dot].is_some())
+            .collect()
+    }
+
+    #[test]
+    fn affine_objects_use_up_the_cycle_budget() {
+        let mut ppu = ppu();
+        // Each 64 dot wide affine object takes 10 + 2 * 64 cycles; six of them off screen
+        //   leave 126 of the 954 cycles, enough for 58 dots of the seventh
+        for index in 0..6 {
+            set_obj(&mut ppu, index, [ATTR0_AFFINE, ATTR1_SIZE_64 | 300, 0]);
+        }
+        set_obj(&mut ppu, 6, [ATTR0_AFFINE, ATTR1_SIZE_64, 0]);
+        set_obj(&mut ppu, 7, [ATTR0_AFFINE, ATTR1_SIZE_64 | 100, 0]);
+
+        let line = ppu.render_objects(DISPCNT_HBLANK_FREE, &NO_MOSAIC, 0, 0);
+        assert_eq!(drawn(&line), (0..58).collect::<Vec<_>>());
+
+        // Without H-Blank interval free, both fit
+        let line = ppu.render_objects(0, &NO_MOSAIC, 0, 0);
+        let expected: Vec<usize> = (0..64).chain(100..164).collect();
+        assert_eq!(drawn(&line), expected);
+    }
+
+    #[test]
+    fn double_size_affine_objects_cover_twice_the_area() {
+        let mut ppu = ppu();
+        // A 64x64 image at identity sits in the middle of its 128x128 bounds, which start
+        //   at Y 200 and wrap around past line 255
+        set_obj(
+            &mut ppu,
+            0,
+            [ATTR0_AFFINE | (1 << 9) | 200, ATTR1_SIZE_64, 0],
+        );
+        let line = ppu.render_objects(0, &NO_MOSAIC, 0, 210);
+        assert!(drawn(&line).is_empty());
+        let line = ppu.render_objects(0, &NO_MOSAIC, 0, 30);
+        assert_eq!(drawn(&line), (32..96).collect::<Vec<_>>());
+        let line = ppu.render_objects(0, &NO_MOSAIC, 0, 80);
+        assert!(drawn(&line).is_empty());
+    }
+
+    #[test]
+    fn two_dimensional_256_color_mapping_ignores_the_low_tile_bit() {
+        let mut ppu = ppu();
+        ppu.vram[OBJ_TILE_BASE..].fill(0);
+        // Tiles 2 and 3 start with colors 5 and 9, and tile 2's right neighbour in the
+        //   second row of the 32 tile wide grid, tile 36, with color 7
+        ppu.vram[OBJ_TILE_BASE + 2 * 32] = 5;
+        ppu.vram[OBJ_TILE_BASE + 3 * 32] = 9;
+        ppu.vram[OBJ_TILE_BASE + 36 * 32] = 7;
+        for color in [5, 7, 9] {
+            set_obj_color(&mut ppu, color, color as u16);
+        }
+        set_obj(&mut ppu, 0, [ATTR0_256_COLORS, ATTR1_SIZE_16, 3]);
+
+        let line = ppu.render_objects(0, &NO_MOSAIC, 0, 0);
+        assert_eq!(drawn(&line), vec![0]);
+        assert_eq!(line.pixels[0].unwrap().color, 5);
+        let line = ppu.render_objects(0, &NO_MOSAIC, 0, 8);
+        assert_eq!(drawn(&line), vec![8]);
+        assert_eq!(line.pixels[8].unwrap().color, 7);
+
+        // 1D mapping takes the tile number as it is
+        let line = ppu.render_objects(DISPCNT_OBJ_1D, &NO_MOSAIC, 0, 0);
+        assert_eq!(line.pixels[0].unwrap().color, 9);
+    }
+
+    #[test]
+    fn lower_numbered_objects_win_at_equal_priority() {
+        let mut ppu = ppu();
+        // Color 1 of palette banks 1 and 2
+        set_obj_color(&mut ppu, 17, 0x001f);
+        set_obj_color(&mut ppu, 33, 0x03e0);
+        set_obj(&mut ppu, 0, [0, 10, (1 << 12) | (1 << 10)]);
+        set_obj(&mut ppu, 1, [0, 14, (2 << 12) | (1 << 10)]);
+
+        let line = ppu.render_objects(0, &NO_MOSAIC, 0, 0);
+        assert_eq!(line.pixels[10].unwrap().color, 0x001f);
+        assert_eq!(line.pixels[17].unwrap().color, 0x001f);
+        assert_eq!(line.pixels[18].unwrap().color, 0x03e0);
+
+        // A higher priority wins regardless of the order
+        set_obj(&mut ppu, 1, [0, 14, 2 << 12]);
+        let line = ppu.render_objects(0, &NO_MOSAIC, 0, 0);
+        assert_eq!(line.pixels[14].unwrap().color, 0x03e0);
+        assert_eq!(line.pixels[10].unwrap().color, 0x001f);
+    }
+}
@@ -0,0 +1,69 @@
+use super::background::BgControl;
+use super::{Ppu, SCREEN_WIDTH};
+use crate::io::{IoRegisters, BG0HOFS};
+use crate::util::get_bits;
+
+/// A screen block holds the 32x32 map entries of a 256x256 dot area.
+const SCREEN_BLOCK_SIZE: usize = 0x800;
+
+impl Ppu {
+    /// Draws one scanline of a regular tiled background. Larger maps are made of 2 or 4
+    ///   screen blocks, laid out left to right then top to bottom.
+    pub(super) fn render_text_line<C>(
+        &self,
+        io: &IoRegisters<C>,
+        bg: usize,
+        control: &BgControl,
+        vcount: usize,
+        line: &mut [Option<u16>; SCREEN_WIDTH],
+    ) {
+        let hofs = io.get(BG0HOFS + 4 * bg as u32) as usize;
+        let vofs = io.get(BG0HOFS + 4 * bg as u32 + 2) as usize;
+        let (width, height) = match control.size {
+            0 => (256, 256),
+            1 => (512, 256),
+            2 => (256, 512),
+            _ => (512, 512),
+        };
+        let tile_bytes = if control.color_256 { 64 } else { 32 };
+
+        let y = (vcount + vofs) % height;
+        for (dot, pixel) in line.iter_mut().enumerate() {
+            let x = (dot + hofs) % width;
+
+            let block = (y / 256) * (width / 256) + x / 256;
+            let entry_address = control.screen_base
+                + block * SCREEN_BLOCK_SIZE
+                + ((y % 256) / 8 * 32 + (x % 256) / 8) * 2;
+            let entry =
+                u16::from_le_bytes([self.vram[entry_address], self.vram[entry_address + 1]]) as u32;
+
+            let tile = get_bits(entry, 0, 10) as usize;
+            let tile_x = if get_bits(entry, 10, 1) != 0 {
+                7 - x % 8
+            } else {
+                x % 8
+            };
+            let tile_y = if get_bits(entry, 11, 1) != 0 {
+                7 - y % 8
+            } else {
+                y % 8
+            };
+            let color = self.tile_dot(
+                control.char_base + tile * tile_bytes,
+                control.color_256,
+                tile_x,
+                tile_y,
+            );
+
+            *pixel = match color {
+                0 => None,
+                _ if control.color_256 => Some(self.palette_color(color as usize)),
+                _ => {
+                    let palette_bank = get_bits(entry, 12, 4) as usize;
+                    Some(self.palette_color(palette_bank * 16 + color as usize))
+                }
+            };
+        }
+    }
+}
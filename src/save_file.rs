@@ -0,0 +1,68 @@
+//! Battery save persistence. Natively saves live next to the ROM as `<rom>.sav`; on the web,
+//!   where the ROM is only available as bytes, they are kept in the browser's storage instead.
+
+use std::path::Path;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(_storage: Option<&dyn eframe::Storage>, rompath: &Path) -> Option<Vec<u8>> {
+    std::fs::read(rompath.with_extension("sav")).ok()
+}
+
+/// Writes the save to a temporary file first and renames it into place,
+///   so a crash mid-write leaves the previous save intact.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn store(
+    _storage: Option<&mut dyn eframe::Storage>,
+    rompath: &Path,
+    savebytes: &[u8],
+) -> Result<(), String> {
+    use std::io::Write;
+
+    let savepath = rompath.with_extension("sav");
+    let tmppath = rompath.with_extension("sav.tmp");
+    let write_tmp = || -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&tmppath)?;
+        file.write_all(savebytes)?;
+        file.sync_all()
+    };
+    write_tmp()
+        .and_then(|_| std::fs::rename(&tmppath, &savepath))
+        .map_err(|err| format!("Could not write {}: {}", savepath.display(), err))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load(storage: Option<&dyn eframe::Storage>, rompath: &Path) -> Option<Vec<u8>> {
+    let encoded = storage?.get_string(&storage_key(rompath))?;
+    decode_hex(&encoded)
+}
+
+/// Browser storage replaces a key's value in one step, so no temporary copy is needed.
+#[cfg(target_arch = "wasm32")]
+pub fn store(
+    storage: Option<&mut dyn eframe::Storage>,
+    rompath: &Path,
+    savebytes: &[u8],
+) -> Result<(), String> {
+    let storage = storage.ok_or("Browser storage is unavailable")?;
+    storage.set_string(&storage_key(rompath), encode_hex(savebytes));
+    storage.flush();
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn storage_key(rompath: &Path) -> String {
+    format!("battery_save:{}", rompath.display())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn decode_hex(encoded: &str) -> Option<Vec<u8>> {
+    (0..encoded.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(encoded.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,168 @@
+use crate::interrupt::{self, Interrupt};
+use crate::io::{IoRegisters, IoWrite, TM0CNT_H, TM0CNT_L};
+use crate::memory::Memory;
+use crate::util::get_bits;
+
+pub const NUM_TIMERS: usize = 4;
+
+/// CPU cycles per tick for each prescaler setting.
+const PRESCALER_CYCLES: [u32; 4] = [1, 64, 256, 1024];
+const INTERRUPTS: [Interrupt; NUM_TIMERS] = [
+    Interrupt::Timer0,
+    Interrupt::Timer1,
+    Interrupt::Timer2,
+    Interrupt::Timer3,
+];
+
+const TMCNT_COUNT_UP: u32 = 1 << 2;
+const TMCNT_IRQ: u32 = 1 << 6;
+const TMCNT_ENABLE: u32 = 1 << 7;
+
+/// How fast a timer overflows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimerRate {
+    Stopped,
+    /// Counting overflows of the timer before it, rather than CPU cycles.
+    CountUp,
+    Hz(f64),
+}
+
+/// One 16-bit timer. It counts up from its reload value and reloads when it overflows.
+#[derive(Debug, Default, Clone, Copy)]
+struct Timer {
+    counter: u16,
+    reload: u16,
+    control: u32,
+    // CPU cycles counted towards the next prescaled tick
+    cycles: u32,
+}
+
+impl Timer {
+    fn enabled(&self) -> bool {
+        self.control & TMCNT_ENABLE != 0
+    }
+
+    /// Adds `ticks` to the counter, returning how many times it overflowed.
+    fn count(&mut self, mut ticks: u32) -> u32 {
+        let mut overflows = 0;
+        while ticks > 0 {
+            let until_overflow = 0x10000 - self.counter as u32;
+            if ticks < until_overflow {
+                self.counter += ticks as u16;
+                break;
+            }
+            ticks -= until_overflow;
+            self.counter = self.reload;
+            overflows += 1;
+        }
+        overflows
+    }
+}
+
+/// The four timers. Timer 0 and 1 also pace the Direct Sound FIFOs, and each of timers
+///   1-3 can count the overflows of the one before it instead of CPU cycles.
+#[derive(Debug, Default)]
+pub struct Timers {
+    timers: [Timer; NUM_TIMERS],
+}
+
+impl Timers {
+    /// TMxCNT_L sets the reload value on writes, but reads back the live counter.
+    pub fn register_io_hooks(io: &mut IoRegisters<Memory>) {
+        io.on_read(TM0CNT_L, |memory| memory.timers.timers[0].counter as u32);
+        io.on_read(TM0CNT_L + 4, |memory| {
+            memory.timers.timers[1].counter as u32
+        });
+        io.on_read(TM0CNT_L + 8, |memory| {
+            memory.timers.timers[2].counter as u32
+        });
+        io.on_read(TM0CNT_L + 12, |memory| {
+            memory.timers.timers[3].counter as u32
+        });
+
+        io.on_write(TM0CNT_L, |memory, write| {
+            memory.timers.write_reload(0, write)
+        });
+        io.on_write(TM0CNT_L + 4, |memory, write| {
+            memory.timers.write_reload(1, write)
+        });
+        io.on_write(TM0CNT_L + 8, |memory, write| {
+            memory.timers.write_reload(2, write)
+        });
+        io.on_write(TM0CNT_L + 12, |memory, write| {
+            memory.timers.write_reload(3, write)
+        });
+
+        io.on_write(TM0CNT_H, |memory, write| {
+            memory.timers.write_control(0, write)
+        });
+        io.on_write(TM0CNT_H + 4, |memory, write| {
+            memory.timers.write_control(1, write)
+        });
+        io.on_write(TM0CNT_H + 8, |memory, write| {
+            memory.timers.write_control(2, write)
+        });
+        io.on_write(TM0CNT_H + 12, |memory, write| {
+            memory.timers.write_control(3, write)
+        });
+    }
+
+    fn write_reload(&mut self, index: usize, write: IoWrite) -> u32 {
+        self.timers[index].reload = write.value as u16;
+        write.value
+    }
+
+    /// Starting a timer loads its counter with the reload value.
+    fn write_control(&mut self, index: usize, write: IoWrite) -> u32 {
+        let timer = &mut self.timers[index];
+        let starting = !timer.enabled() && write.value & TMCNT_ENABLE != 0;
+        timer.control = write.value;
+        if starting {
+            timer.counter = timer.reload;
+            timer.cycles = 0;
+        }
+        write.value
+    }
+
+    /// How often a timer overflows, if it is counting CPU cycles.
+    pub fn overflow_rate(&self, index: usize) -> TimerRate {
+        let timer = &self.timers[index];
+        if !timer.enabled() {
+            return TimerRate::Stopped;
+        }
+        if index > 0 && timer.control & TMCNT_COUNT_UP != 0 {
+            return TimerRate::CountUp;
+        }
+        let prescaler = PRESCALER_CYCLES[get_bits(timer.control, 0, 2) as usize];
+        let ticks = 0x10000 - timer.reload as u32;
+        TimerRate::Hz(crate::apu::CPU_CLOCK_HZ as f64 / (prescaler * ticks) as f64)
+    }
+
+    /// Runs the timers for `cycles` CPU cycles, returning how often each overflowed.
+    pub fn step<C>(&mut self, io: &mut IoRegisters<C>, cycles: usize) -> [u32; NUM_TIMERS] {
+        let mut overflows = [0; NUM_TIMERS];
+        for index in 0..NUM_TIMERS {
+            let timer = &mut self.timers[index];
+            if !timer.enabled() {
+                continue;
+            }
+
+            // Timer 0 has nothing to count up from, so it ignores the setting
+            let ticks = if index > 0 && timer.control & TMCNT_COUNT_UP != 0 {
+                overflows[index - 1]
+            } else {
+                let prescaler = PRESCALER_CYCLES[get_bits(timer.control, 0, 2) as usize];
+                timer.cycles += cycles as u32;
+                let ticks = timer.cycles / prescaler;
+                timer.cycles %= prescaler;
+                ticks
+            };
+
+            overflows[index] = timer.count(ticks);
+            if overflows[index] > 0 && timer.control & TMCNT_IRQ != 0 {
+                interrupt::request(io, INTERRUPTS[index]);
+            }
+        }
+        overflows
+    }
+}
@@ -0,0 +1,88 @@
+use egui::Color32;
+
+/// Number of distinct BGR555 colors.
+const NUM_COLORS: usize = 0x8000;
+
+/// Approximations of how each screen shows the GBA's colors. The raw colors look far too
+///   saturated on a modern monitor, since the original LCDs are darker and their color
+///   channels bleed into each other.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum ColorCorrection {
+    #[default]
+    None,
+    Gba,
+    /// The backlit AGS-101 model of the GBA SP.
+    GbaSp,
+    GameBoyPlayer,
+}
+
+impl ColorCorrection {
+    pub const ALL: [Self; 4] = [Self::None, Self::Gba, Self::GbaSp, Self::GameBoyPlayer];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Gba => "GBA",
+            Self::GbaSp => "GBA SP (AGS-101)",
+            Self::GameBoyPlayer => "Game Boy Player",
+        }
+    }
+
+    /// The screen's gamma, its brightness, and how much of each input channel ends up in
+    ///   each output channel, one row per output channel.
+    fn response(&self) -> Option<(f32, f32, [[f32; 3]; 3])> {
+        match self {
+            Self::None => None,
+            Self::Gba => Some((
+                3.2,
+                0.94,
+                [
+                    [0.82, 0.24, -0.06],
+                    [0.125, 0.665, 0.21],
+                    [0.195, 0.075, 0.73],
+                ],
+            )),
+            Self::GbaSp => Some((
+                2.4,
+                1.0,
+                [
+                    [0.96, 0.11, -0.07],
+                    [0.0325, 0.89, 0.0775],
+                    [0.001, -0.03, 1.029],
+                ],
+            )),
+            Self::GameBoyPlayer => Some((
+                2.4,
+                0.95,
+                [
+                    [0.86, 0.11, 0.03],
+                    [0.035, 0.93, 0.035],
+                    [0.04, 0.075, 0.885],
+                ],
+            )),
+        }
+    }
+
+    /// The output color for every BGR555 color, indexed by the color.
+    pub fn table(&self) -> Vec<Color32> {
+        (0..NUM_COLORS as u16)
+            .map(|color| self.apply(color))
+            .collect()
+    }
+
+    fn apply(&self, color: u16) -> Color32 {
+        let channel = |shift: u16| ((color >> shift) & 0x1f) as f32 / 31.0;
+        let input = [channel(0), channel(5), channel(10)];
+        let Some((gamma, luminance, matrix)) = self.response() else {
+            return super::bgr555_to_color32(color);
+        };
+
+        // Mix in linear light, then encode for a monitor with a gamma of 2.2
+        let linear = input.map(|value| value.powf(gamma) * luminance);
+        let [r, g, b] = matrix.map(|row| {
+            let value = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+            (value.clamp(0.0, 1.0).powf(1.0 / 2.2) * 255.0).round() as u8
+        });
+        Color32::from_rgb(r, g, b)
+    }
+}
@@ -0,0 +1,235 @@
+use egui::Color32;
+
+/// Pixel art scalers and screen overlays, applied to the frame before it is uploaded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum UpscaleFilter {
+    #[default]
+    None,
+    Scale2x,
+    Scale3x,
+    /// The first level of the xBR rules at 2x, which smooths edges by blending instead of
+    ///   only picking neighbors.
+    Xbr2x,
+    /// Dark gaps between the dots, like the LCD's pixel grid.
+    LcdGrid,
+    Scanlines,
+}
+
+impl UpscaleFilter {
+    pub const ALL: [Self; 6] = [
+        Self::None,
+        Self::Scale2x,
+        Self::Scale3x,
+        Self::Xbr2x,
+        Self::LcdGrid,
+        Self::Scanlines,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Scale2x => "Scale2x",
+            Self::Scale3x => "Scale3x",
+            Self::Xbr2x => "2xBR",
+            Self::LcdGrid => "LCD grid",
+            Self::Scanlines => "Scanlines",
+        }
+    }
+
+    /// How many output pixels each input pixel becomes in each direction.
+    pub fn factor(&self) -> usize {
+        match self {
+            Self::None => 1,
+            Self::Scale2x | Self::Xbr2x | Self::Scanlines => 2,
+            Self::Scale3x | Self::LcdGrid => 3,
+        }
+    }
+
+    /// Scales a `width` x `height` image by `factor`.
+    pub fn apply(&self, pixels: &[Color32], width: usize, height: usize) -> Vec<Color32> {
+        let scaler = match self {
+            Self::None => return pixels.to_vec(),
+            Self::Scale2x => scale2x,
+            Self::Scale3x => scale3x,
+            Self::Xbr2x => xbr2x,
+            Self::LcdGrid => lcd_grid,
+            Self::Scanlines => scanlines,
+        };
+        let source = Source {
+            pixels,
+            width,
+            height,
+        };
+        let factor = self.factor();
+        let mut output = vec![Color32::BLACK; pixels.len() * factor * factor];
+        let out_width = width * factor;
+
+        for y in 0..height {
+            for x in 0..width {
+                let block = scaler(&source, x, y);
+                for (row, chunk) in block[..factor * factor].chunks(factor).enumerate() {
+                    let start = (y * factor + row) * out_width + x * factor;
+                    output[start..start + factor].copy_from_slice(chunk);
+                }
+            }
+        }
+        output
+    }
+}
+
+/// The output block for one input pixel, row by row. Only the first `factor` squared
+///   entries are used.
+type Block = [Color32; 9];
+
+struct Source<'a> {
+    pixels: &'a [Color32],
+    width: usize,
+    height: usize,
+}
+
+impl Source<'_> {
+    /// The pixel at an offset from (`x`, `y`), repeating the edges past the borders.
+    fn at(&self, x: usize, y: usize, dx: i32, dy: i32) -> Color32 {
+        let x = (x as i32 + dx).clamp(0, self.width as i32 - 1) as usize;
+        let y = (y as i32 + dy).clamp(0, self.height as i32 - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Scale2x: each corner takes the color of its two neighbors when they agree and the
+///   opposite neighbors don't.
+fn scale2x(source: &Source<'_>, x: usize, y: usize) -> Block {
+    let e = source.at(x, y, 0, 0);
+    let b = source.at(x, y, 0, -1);
+    let d = source.at(x, y, -1, 0);
+    let f = source.at(x, y, 1, 0);
+    let h = source.at(x, y, 0, 1);
+
+    let mut block = [e; 9];
+    if b != h && d != f {
+        block[0] = if d == b { d } else { e };
+        block[1] = if b == f { f } else { e };
+        block[2] = if d == h { d } else { e };
+        block[3] = if h == f { f } else { e };
+    }
+    block
+}
+
+fn scale3x(source: &Source<'_>, x: usize, y: usize) -> Block {
+    let a = source.at(x, y, -1, -1);
+    let b = source.at(x, y, 0, -1);
+    let c = source.at(x, y, 1, -1);
+    let d = source.at(x, y, -1, 0);
+    let e = source.at(x, y, 0, 0);
+    let f = source.at(x, y, 1, 0);
+    let g = source.at(x, y, -1, 1);
+    let h = source.at(x, y, 0, 1);
+    let i = source.at(x, y, 1, 1);
+
+    let mut block = [e; 9];
+    if b != h && d != f {
+        block[0] = if d == b { d } else { e };
+        block[1] = if (d == b && e != c) || (b == f && e != a) {
+            b
+        } else {
+            e
+        };
+        block[2] = if b == f { f } else { e };
+        block[3] = if (d == b && e != g) || (d == h && e != a) {
+            d
+        } else {
+            e
+        };
+        block[5] = if (b == f && e != i) || (h == f && e != c) {
+            f
+        } else {
+            e
+        };
+        block[6] = if d == h { d } else { e };
+        block[7] = if (d == h && e != i) || (h == f && e != g) {
+            h
+        } else {
+            e
+        };
+        block[8] = if h == f { f } else { e };
+    }
+    block
+}
+
+/// 2xBR: each corner of the block looks for an edge running across it, comparing the
+///   color differences along both diagonals of the surrounding 4x4 area, and blends in
+///   the nearer neighbor when the edge is the stronger one.
+fn xbr2x(source: &Source<'_>, x: usize, y: usize) -> Block {
+    let e = source.at(x, y, 0, 0);
+    let mut block = [e; 9];
+
+    // Rotating the neighborhood lets the bottom right corner's rule serve all four
+    for (corner, rotation) in [(3, 0), (2, 1), (0, 2), (1, 3)] {
+        let n = |dx: i32, dy: i32| {
+            let (dx, dy) = (0..rotation).fold((dx, dy), |(dx, dy), _| (-dy, dx));
+            source.at(x, y, dx, dy)
+        };
+        let (f, h) = (n(1, 0), n(0, 1));
+        if e == f || e == h {
+            continue;
+        }
+
+        let edge = distance(e, n(1, -1))
+            + distance(e, n(-1, 1))
+            + distance(n(1, 1), n(2, 0))
+            + distance(n(1, 1), n(0, 2))
+            + 4 * distance(h, f);
+        let across = distance(h, n(-1, 0))
+            + distance(h, n(1, 2))
+            + distance(f, n(2, 1))
+            + distance(f, n(0, -1))
+            + 4 * distance(e, n(1, 1));
+        if edge < across {
+            let nearer = if distance(e, f) <= distance(e, h) {
+                f
+            } else {
+                h
+            };
+            block[corner] = mix(e, nearer, 1, 2);
+        }
+    }
+    block
+}
+
+/// Leaves the right column and bottom row of each block darker.
+fn lcd_grid(source: &Source<'_>, x: usize, y: usize) -> Block {
+    let e = source.at(x, y, 0, 0);
+    let gap = mix(e, Color32::BLACK, 2, 5);
+    [e, e, gap, e, e, gap, gap, gap, gap]
+}
+
+fn scanlines(source: &Source<'_>, x: usize, y: usize) -> Block {
+    let e = source.at(x, y, 0, 0);
+    let dark = mix(e, Color32::BLACK, 1, 2);
+    [e, e, dark, dark, e, e, e, e, e]
+}
+
+/// How different two colors look, weighting brightness above hue as xBR does.
+fn distance(a: Color32, b: Color32) -> u32 {
+    if a == b {
+        return 0;
+    }
+    let r = a.r() as i32 - b.r() as i32;
+    let g = a.g() as i32 - b.g() as i32;
+    let b = a.b() as i32 - b.b() as i32;
+    // YUV differences, scaled by 1000
+    let y = 299 * r + 587 * g + 114 * b;
+    let u = -169 * r - 331 * g + 500 * b;
+    let v = 500 * r - 419 * g - 81 * b;
+    48 * y.unsigned_abs() + 7 * u.unsigned_abs() + 6 * v.unsigned_abs()
+}
+
+/// Moves `a` towards `b` by `amount` / `total`.
+fn mix(a: Color32, b: Color32, amount: u32, total: u32) -> Color32 {
+    let channel = |a: u8, b: u8| ((a as u32 * (total - amount) + b as u32 * amount) / total) as u8;
+    Color32::from_rgb(
+        channel(a.r(), b.r()),
+        channel(a.g(), b.g()),
+        channel(a.b(), b.b()),
+    )
+}